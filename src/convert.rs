@@ -0,0 +1,1019 @@
+//! The printers of the converters between the generated types and JSON
+//! or S-expressions.
+
+use super::*;
+
+/// A format to which `--json` and `--sexp` generate converters. The
+/// converters call the functions of a helper module, which has the same
+/// interface for both formats.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Json,
+    Sexp,
+}
+
+impl Format {
+    /// Returns the name of the helper module.
+    pub(crate) fn module(self) -> &'static str {
+        match self {
+            Format::Json => "Json_codec",
+            Format::Sexp => "Sexp_codec",
+        }
+    }
+
+    /// Returns the helper module definition.
+    pub(crate) fn helpers(self) -> &'static str {
+        match self {
+            Format::Json => JSON_HELPERS,
+            Format::Sexp => SEXP_HELPERS,
+        }
+    }
+
+    /// Returns the OCaml type of values in the format.
+    pub(crate) fn value_type(self) -> &'static str {
+        match self {
+            Format::Json => "Yojson.Safe.t",
+            Format::Sexp => "Sexplib0.Sexp.t",
+        }
+    }
+
+    /// Returns the type that the converters from the format return for values
+    /// of type `type_`: a `result` for JSON, as with ppx_deriving_yojson, and
+    /// the value itself for S-expressions, as with ppx_sexp_conv.
+    pub(crate) fn result_type(self, type_: &str) -> String {
+        match self {
+            Format::Json => format!("({}, string) result", type_),
+            Format::Sexp => type_.to_string(),
+        }
+    }
+
+    /// Returns the name of the converter to the format of type `name`, which
+    /// may be qualified by a module.
+    pub(crate) fn to_function(self, name: &str) -> String {
+        let (module, name) = match name.rsplit_once('.') {
+            Some((module, name)) => (format!("{}.", module), name),
+            None => (String::new(), name),
+        };
+        match self {
+            Format::Json => format!("{}{}_to_yojson", module, name),
+            Format::Sexp => format!("{}sexp_of_{}", module, name),
+        }
+    }
+
+    /// Returns the name of the converter from the format of type `name`, which
+    /// may be qualified by a module.
+    pub(crate) fn of_function(self, name: &str) -> String {
+        match self {
+            Format::Json => format!("{}_of_yojson", name),
+            Format::Sexp => format!("{}_of_sexp", name),
+        }
+    }
+}
+
+/// Writes the function converting values of type `type_` to `format` to
+/// `code`. A list becomes a `repr` at the outermost level.
+#[allow(clippy::too_many_arguments)]
+fn print_to_converter(
+    code: &mut String,
+    options: &Options,
+    format: Format,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader,
+    repr: FieldRepr,
+) -> Result<()> {
+    let module = format.module();
+    match type_.which()? {
+        schema_capnp::type_::Void(()) => write!(code, "{}.unit", module).unwrap(),
+        schema_capnp::type_::Bool(()) => write!(code, "{}.bool", module).unwrap(),
+        schema_capnp::type_::Int8(())
+        | schema_capnp::type_::Int16(())
+        | schema_capnp::type_::Uint8(())
+        | schema_capnp::type_::Uint16(()) => write!(code, "{}.int", module).unwrap(),
+        schema_capnp::type_::Int32(())
+        | schema_capnp::type_::Int64(())
+        | schema_capnp::type_::Uint32(())
+        | schema_capnp::type_::Uint64(()) => {
+            let int_repr = IntRepr::of_type(type_)?.expect("an integer type");
+            let converter = format!("{}.{}", module, int_repr.name());
+            match int_repr.encoder(repr.int(options, int_repr)) {
+                Some(function) => {
+                    write!(code, "(fun n -> {} ({} n))", converter, function).unwrap()
+                }
+                None => write!(code, "{}", converter).unwrap(),
+            }
+        }
+        schema_capnp::type_::Float32(()) | schema_capnp::type_::Float64(()) => {
+            write!(code, "{}.float", module).unwrap()
+        }
+        schema_capnp::type_::Text(()) => write!(code, "{}.string", module).unwrap(),
+        schema_capnp::type_::Data(()) => match options.data_repr.encoder() {
+            Some(function) => write!(code, "(fun d -> {}.data ({} d))", module, function).unwrap(),
+            None => write!(code, "{}.data", module).unwrap(),
+        },
+        schema_capnp::type_::Interface(_) => write!(code, "{}.capability", module).unwrap(),
+        schema_capnp::type_::List(list) => {
+            write!(code, "({}.{} ", module, repr.list(options).name()).unwrap();
+            print_to_converter(
+                code,
+                options,
+                format,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+                FieldRepr::Other,
+            )?;
+            write!(code, ")").unwrap();
+        }
+        schema_capnp::type_::Enum(enum_) => {
+            let name = get_name(node_name_map, enum_.get_type_id())?;
+            write!(code, "{}", format.to_function(name)).unwrap();
+        }
+        schema_capnp::type_::Struct(struct_) => {
+            let name = get_name(node_name_map, struct_.get_type_id())?;
+            let arguments = brand_arguments(node_map, struct_)?;
+            if arguments.is_empty() {
+                write!(code, "{}", format.to_function(name)).unwrap();
+            } else {
+                write!(code, "({}", format.to_function(name)).unwrap();
+                for argument in arguments {
+                    write!(code, " ").unwrap();
+                    match argument {
+                        BrandArgument::Type(type_) => print_to_converter(
+                            code,
+                            options,
+                            format,
+                            node_map,
+                            node_name_map,
+                            param_env,
+                            type_,
+                            FieldRepr::Other,
+                        )?,
+                        BrandArgument::Inherited(scope_id, index) => {
+                            let name = parameter_name(param_env, scope_id, index)?;
+                            write!(code, "{}", format.to_function(name)).unwrap();
+                        }
+                        BrandArgument::Unbound => return Err(Error::new(UNTYPED_CONVERSION_ERROR)),
+                    }
+                }
+                write!(code, ")").unwrap();
+            }
+        }
+        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which()? {
+            schema_capnp::type_::any_pointer::Parameter(parameter) => {
+                let name = parameter_name(
+                    param_env,
+                    parameter.get_scope_id(),
+                    parameter.get_parameter_index(),
+                )?;
+                write!(code, "{}", format.to_function(name)).unwrap();
+            }
+            schema_capnp::type_::any_pointer::Unconstrained(_) => {
+                return Err(Error::new(UNTYPED_CONVERSION_ERROR))
+            }
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                return Err(Error::new("implicit method parameters are not supported"))
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Writes the function converting values of type `type_` from `format` to
+/// `code`. The function raises the helper module's error if `raising` is set,
+/// and returns the format's result type otherwise. A list becomes a `repr` at
+/// the outermost level.
+#[allow(clippy::too_many_arguments)]
+fn print_of_converter(
+    code: &mut String,
+    options: &Options,
+    format: Format,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader,
+    repr: FieldRepr,
+    raising: bool,
+) -> Result<()> {
+    let module = format.module();
+    // The helpers raise, while the converters of named types and generic
+    // parameters return results.
+    let helper = |name: &str| (format!("{}.{}", module, name), true);
+    let (converter, raises) = match type_.which()? {
+        schema_capnp::type_::Void(()) => helper("to_unit"),
+        schema_capnp::type_::Bool(()) => helper("to_bool"),
+        schema_capnp::type_::Int8(())
+        | schema_capnp::type_::Int16(())
+        | schema_capnp::type_::Uint8(())
+        | schema_capnp::type_::Uint16(()) => helper("to_int"),
+        schema_capnp::type_::Int32(())
+        | schema_capnp::type_::Int64(())
+        | schema_capnp::type_::Uint32(())
+        | schema_capnp::type_::Uint64(()) => {
+            let int_repr = IntRepr::of_type(type_)?.expect("an integer type");
+            let converter = format!("{}.to_{}", module, int_repr.name());
+            match int_repr.decoder(repr.int(options, int_repr)) {
+                Some((function, false)) => {
+                    (format!("(fun j -> {} ({} j))", function, converter), true)
+                }
+                // Integers out of the range of their representation are
+                // invalid in the format.
+                Some((function, true)) => (
+                    format!(
+                        "(fun j -> try {} ({} j) with Invalid_argument e -> {}.fail e j)",
+                        function, converter, module
+                    ),
+                    true,
+                ),
+                None => (converter, true),
+            }
+        }
+        schema_capnp::type_::Float32(()) | schema_capnp::type_::Float64(()) => helper("to_float"),
+        // Strings that are not UTF-8 are invalid in the format.
+        schema_capnp::type_::Text(()) if options.validate_utf8 => (
+            format!(
+                "(fun j -> let s = {}.to_string j in \
+                 if String.is_valid_utf_8 s then s else {}.fail \"Invalid UTF-8\" j)",
+                module, module
+            ),
+            true,
+        ),
+        schema_capnp::type_::Text(()) => helper("to_string"),
+        schema_capnp::type_::Data(()) => match options.data_repr.decoder() {
+            Some(function) => (
+                format!("(fun j -> {} ({}.to_data j))", function, module),
+                true,
+            ),
+            None => helper("to_data"),
+        },
+        schema_capnp::type_::Interface(_) => helper("to_capability"),
+        schema_capnp::type_::List(list) => {
+            let mut converter = format!("({}.to_{} ", module, repr.list(options).name());
+            print_of_converter(
+                &mut converter,
+                options,
+                format,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+                FieldRepr::Other,
+                true,
+            )?;
+            converter.push(')');
+            (converter, true)
+        }
+        schema_capnp::type_::Enum(enum_) => (
+            format.of_function(get_name(node_name_map, enum_.get_type_id())?),
+            false,
+        ),
+        schema_capnp::type_::Struct(struct_) => {
+            let name = get_name(node_name_map, struct_.get_type_id())?;
+            let arguments = brand_arguments(node_map, struct_)?;
+            let mut converter = format.of_function(name);
+            if !arguments.is_empty() {
+                converter = format!("({}", converter);
+                for argument in arguments {
+                    converter.push(' ');
+                    match argument {
+                        BrandArgument::Type(type_) => print_of_converter(
+                            &mut converter,
+                            options,
+                            format,
+                            node_map,
+                            node_name_map,
+                            param_env,
+                            type_,
+                            FieldRepr::Other,
+                            false,
+                        )?,
+                        BrandArgument::Inherited(scope_id, index) => converter.push_str(
+                            &format.of_function(parameter_name(param_env, scope_id, index)?),
+                        ),
+                        BrandArgument::Unbound => return Err(Error::new(UNTYPED_CONVERSION_ERROR)),
+                    }
+                }
+                converter.push(')');
+            }
+            (converter, false)
+        }
+        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which()? {
+            schema_capnp::type_::any_pointer::Parameter(parameter) => (
+                format.of_function(parameter_name(
+                    param_env,
+                    parameter.get_scope_id(),
+                    parameter.get_parameter_index(),
+                )?),
+                false,
+            ),
+            schema_capnp::type_::any_pointer::Unconstrained(_) => {
+                return Err(Error::new(UNTYPED_CONVERSION_ERROR))
+            }
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                return Err(Error::new("implicit method parameters are not supported"))
+            }
+        },
+    };
+    match (raises, raising) {
+        (true, false) => write!(code, "({}.lift {})", module, converter).unwrap(),
+        (false, true) => write!(code, "({}.get {})", module, converter).unwrap(),
+        _ => write!(code, "{}", converter).unwrap(),
+    }
+    Ok(())
+}
+
+/// Returns the expression converting the value `value` of slot field `field`
+/// to `format`.
+#[allow(clippy::too_many_arguments)]
+fn slot_to_format(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    format: Format,
+    field: schema_capnp::field::Reader,
+    slot: schema_capnp::field::slot::Reader,
+    value: &str,
+) -> Result<String> {
+    let mut converter = String::new();
+    print_to_converter(
+        &mut converter,
+        options,
+        format,
+        node_map,
+        node_name_map,
+        Some(param_env),
+        slot.get_type()?,
+        FieldRepr::of_field(options, field)?,
+    )?;
+    if field.get_discriminant_value() == NO_DISCRIMINANT && is_optional(options, slot)? {
+        converter = format!("({}.option {})", format.module(), converter);
+    }
+    Ok(format!("{} {}", converter, value))
+}
+
+/// Returns the expression converting slot field `field` from its
+/// representation `value` in `format`. A missing or null field has its
+/// explicit default, if any, or else the value converted from null, which is
+/// the implicit default.
+#[allow(clippy::too_many_arguments)]
+fn slot_of_format(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    format: Format,
+    field: schema_capnp::field::Reader,
+    slot: schema_capnp::field::slot::Reader,
+    value: &str,
+) -> Result<String> {
+    let module = format.module();
+    let type_ = slot.get_type()?;
+    let repr = FieldRepr::of_field(options, field)?;
+    let mut converter = String::new();
+    print_of_converter(
+        &mut converter,
+        options,
+        format,
+        node_map,
+        node_name_map,
+        Some(param_env),
+        type_,
+        repr,
+        true,
+    )?;
+    if field.get_discriminant_value() == NO_DISCRIMINANT && is_optional(options, slot)? {
+        converter = format!("({}.to_option {})", module, converter);
+    }
+    if !slot.get_had_explicit_default() {
+        return Ok(format!("{} {}", converter, value));
+    }
+    let default_value = slot.get_default_value()?;
+    let default = match (repr, default_value.which()?, type_.which()?) {
+        (FieldRepr::Int(repr), schema_capnp::value::Int32(x), _) => repr.literal(x.into())?,
+        (FieldRepr::Int(repr), schema_capnp::value::Int64(x), _) => repr.literal(x.into())?,
+        (FieldRepr::Int(repr), schema_capnp::value::Uint32(x), _) => repr.literal(x.into())?,
+        (FieldRepr::Int(repr), schema_capnp::value::Uint64(x), _) => repr.literal(x.into())?,
+        (
+            FieldRepr::List(repr),
+            schema_capnp::value::List(list),
+            schema_capnp::type_::List(list_type),
+        ) => list_literal(
+            options,
+            node_map,
+            node_name_map,
+            list_type.get_element_type()?,
+            list,
+            repr,
+        )?,
+        _ => constant_literal(options, node_map, node_name_map, type_, default_value)?,
+    };
+    Ok(format!(
+        "{}.default (fun () -> {}) {} {}",
+        module, default, converter, value
+    ))
+}
+
+/// Returns the converter arguments to pass to a converter defined in
+/// `param_env`, named by `function` (e.g. ` t_to_yojson`).
+fn converter_args(param_env: &ParamEnv<'_>, function: impl Fn(&str) -> String) -> String {
+    param_env
+        .type_parameters()
+        .iter()
+        .map(|name| format!(" {}", function(name)))
+        .collect()
+}
+
+/// Returns the members of the object representing struct or group `node_id`
+/// in `format`, as OCaml pairs of a name and a value. The record holding the
+/// fields is `value`, and the value of the union, if any, is `union_value`.
+/// Groups are nested objects, as in Cap'n Proto's JSON mapping, even if they
+/// are inlined.
+#[allow(clippy::too_many_arguments)]
+fn to_members(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    format: Format,
+    node_id: u64,
+    value: &str,
+    union_value: &str,
+) -> Result<Vec<String>> {
+    let module = format.module();
+    let node = get_node(node_map, node_id)?;
+    let struct_node = get_struct_node(node)?;
+    let mut members = Vec::new();
+    let mut union_cases = String::new();
+    for field in struct_node.get_fields()?.iter() {
+        let name = field.get_name()?.to_str()?;
+        if field.get_discriminant_value() != NO_DISCRIMINANT {
+            let constructor =
+                member_constructor(node_map, field).map_err(|error| error.in_member(name))?;
+            let member = match field.which()? {
+                schema_capnp::field::Slot(slot) => {
+                    if let schema_capnp::type_::Void(()) = slot.get_type()?.which()? {
+                        write!(
+                            union_cases,
+                            "\n      | {} -> (\"{}\", {}.unit ())",
+                            constructor, name, module
+                        )
+                        .unwrap();
+                        continue;
+                    }
+                    slot_to_format(
+                        options,
+                        node_map,
+                        node_name_map,
+                        param_env,
+                        format,
+                        field,
+                        slot,
+                        "v'",
+                    )
+                    .map_err(|error| error.in_member(name))?
+                }
+                schema_capnp::field::Group(group) => {
+                    let group_node = get_node(node_map, group.get_type_id())?;
+                    if is_inline_group(get_struct_node(group_node)?) {
+                        let members = to_members(
+                            options,
+                            node_map,
+                            node_name_map,
+                            param_env,
+                            format,
+                            group.get_type_id(),
+                            "v'",
+                            "",
+                        )
+                        .map_err(|error| error.in_node(group_node))?;
+                        format!("{}.obj [{}]", module, members.join("; "))
+                    } else {
+                        format!(
+                            "{}{} v'",
+                            format.to_function(get_name(node_name_map, group.get_type_id())?),
+                            converter_args(param_env, |name| format.to_function(name))
+                        )
+                    }
+                }
+            };
+            write!(
+                union_cases,
+                "\n      | {} v' -> (\"{}\", {})",
+                constructor, name, member
+            )
+            .unwrap();
+            continue;
+        }
+        if has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        let label = field_label(node_map, field).map_err(|error| error.in_member(name))?;
+        let field_value = format!("{}.{}", value, label);
+        let member = match field.which()? {
+            schema_capnp::field::Slot(slot) => slot_to_format(
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                format,
+                field,
+                slot,
+                &field_value,
+            )
+            .map_err(|error| error.in_member(name))?,
+            schema_capnp::field::Group(group) => {
+                let group_node = get_node(node_map, group.get_type_id())?;
+                if is_inlined(field, get_struct_node(group_node)?)? {
+                    let members = to_members(
+                        options,
+                        node_map,
+                        node_name_map,
+                        param_env,
+                        format,
+                        group.get_type_id(),
+                        value,
+                        "",
+                    )
+                    .map_err(|error| error.in_node(group_node))?;
+                    format!("{}.obj [{}]", module, members.join("; "))
+                } else {
+                    format!(
+                        "{}{} {}",
+                        format.to_function(get_name(node_name_map, group.get_type_id())?),
+                        converter_args(param_env, |name| format.to_function(name)),
+                        field_value
+                    )
+                }
+            }
+        };
+        members.push(format!("(\"{}\", {})", name, member));
+    }
+    if !union_cases.is_empty() {
+        // A union becomes the member for its active field, and an unknown
+        // one becomes member `unknown`, holding the discriminant.
+        if options.unknown {
+            write!(
+                union_cases,
+                "\n      | Unknown n -> (\"unknown\", {}.int n)",
+                module
+            )
+            .unwrap();
+        }
+        members.push(format!("(match {} with{})", union_value, union_cases));
+    }
+    Ok(members)
+}
+
+/// Returns the fields of the record representing struct or group `node_id`,
+/// converted from its representation `value` in `format`, as OCaml field
+/// bindings, leaving out its union.
+#[allow(clippy::too_many_arguments)]
+fn of_fields(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    format: Format,
+    node_id: u64,
+    value: &str,
+) -> Result<Vec<String>> {
+    let module = format.module();
+    let struct_node = get_struct_node(get_node(node_map, node_id)?)?;
+    let mut fields = Vec::new();
+    for field in struct_node.get_fields()?.iter() {
+        if field.get_discriminant_value() != NO_DISCRIMINANT
+            || has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)?
+        {
+            continue;
+        }
+        let name = field.get_name()?.to_str()?;
+        let label = field_label(node_map, field).map_err(|error| error.in_member(name))?;
+        let field_value = format!("({}.field \"{}\" {})", module, name, value);
+        match field.which()? {
+            schema_capnp::field::Slot(slot) => {
+                let conversion = slot_of_format(
+                    options,
+                    node_map,
+                    node_name_map,
+                    param_env,
+                    format,
+                    field,
+                    slot,
+                    &field_value,
+                )
+                .map_err(|error| error.in_member(name))?;
+                fields.push(format!("{} = {}", label, conversion));
+            }
+            schema_capnp::field::Group(group) => {
+                let group_node = get_node(node_map, group.get_type_id())?;
+                if is_inlined(field, get_struct_node(group_node)?)? {
+                    fields.extend(
+                        of_fields(
+                            options,
+                            node_map,
+                            node_name_map,
+                            param_env,
+                            format,
+                            group.get_type_id(),
+                            &field_value,
+                        )
+                        .map_err(|error| error.in_node(group_node))?,
+                    );
+                } else {
+                    fields.push(format!(
+                        "{} = {}",
+                        label,
+                        group_of_format(node_name_map, param_env, format, group, &field_value)?
+                    ));
+                }
+            }
+        }
+    }
+    Ok(fields)
+}
+
+/// Returns the expression converting group `group`, which has a type of its
+/// own, from its representation `value` in `format`.
+fn group_of_format(
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    format: Format,
+    group: schema_capnp::field::group::Reader,
+    value: &str,
+) -> Result<String> {
+    let converter = format.of_function(get_name(node_name_map, group.get_type_id())?);
+    let args = converter_args(param_env, |name| format.of_function(name));
+    Ok(if args.is_empty() {
+        format!("{}.get {} {}", format.module(), converter, value)
+    } else {
+        format!("{}.get ({}{}) {}", format.module(), converter, args, value)
+    })
+}
+
+/// Returns the expression converting the union of struct or group `node` from
+/// the representation `value` of the struct or group in `format`. The active
+/// field is the first union member present in the object. An object without
+/// any is invalid, while `null` stands for the default value, whose active
+/// field is the one with discriminant 0. With `--unknown`, member `unknown`
+/// holds the discriminant of a field that is not in the schema.
+fn union_of_format(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    format: Format,
+    node: schema_capnp::node::Reader,
+    value: &str,
+) -> Result<String> {
+    let module = format.module();
+    let mut names = Vec::new();
+    let mut cases = String::new();
+    let mut default_case = String::new();
+    for field in get_struct_node(node)?.get_fields()?.iter() {
+        if field.get_discriminant_value() == NO_DISCRIMINANT {
+            continue;
+        }
+        let name = field.get_name()?.to_str()?;
+        let constructor =
+            member_constructor(node_map, field).map_err(|error| error.in_member(name))?;
+        let field_value = format!("({}.field \"{}\" {})", module, name, value);
+        let variant = match field.which()? {
+            schema_capnp::field::Slot(slot) => {
+                if let schema_capnp::type_::Void(()) = slot.get_type()?.which()? {
+                    constructor
+                } else {
+                    let conversion = slot_of_format(
+                        options,
+                        node_map,
+                        node_name_map,
+                        param_env,
+                        format,
+                        field,
+                        slot,
+                        &field_value,
+                    )
+                    .map_err(|error| error.in_member(name))?;
+                    format!("{} ({})", constructor, conversion)
+                }
+            }
+            schema_capnp::field::Group(group) => {
+                let group_node = get_node(node_map, group.get_type_id())?;
+                if is_inline_group(get_struct_node(group_node)?) {
+                    let fields = of_fields(
+                        options,
+                        node_map,
+                        node_name_map,
+                        param_env,
+                        format,
+                        group.get_type_id(),
+                        &field_value,
+                    )
+                    .map_err(|error| error.in_node(group_node))?;
+                    format!("{} {{{}}}", constructor, fields.join("; "))
+                } else {
+                    format!(
+                        "{} ({})",
+                        constructor,
+                        group_of_format(node_name_map, param_env, format, group, &field_value)?
+                    )
+                }
+            }
+        };
+        if field.get_discriminant_value() == 0 {
+            default_case = format!("\n      | \"{}\" | \"\" -> {}", name, variant);
+        } else {
+            write!(cases, "\n      | \"{}\" -> {}", name, variant).unwrap();
+        }
+        names.push(format!("\"{}\"", name));
+    }
+    if options.unknown {
+        write!(
+            cases,
+            "\n      | \"unknown\" -> Unknown ({}.to_int ({}.field \"unknown\" {}))",
+            module, module, value
+        )
+        .unwrap();
+        names.push("\"unknown\"".to_string());
+    }
+    Ok(format!(
+        "(match {}.member [{}] {} with{}{}\n      | _ -> {}.fail \"undefined union member\" {})",
+        module,
+        names.join("; "),
+        value,
+        cases,
+        default_case,
+        module,
+        value
+    ))
+}
+
+/// Starts the next converter of the current node.
+fn print_converter_separator(out: &mut Output) {
+    if !out.converters.is_empty() {
+        write!(out.converters, "\nand ").unwrap();
+    }
+}
+
+/// Prints the converters of struct or group `node_id`, whose type is `name`,
+/// to and from each format of `--json` and `--sexp`, and adds their
+/// signatures to the interface.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn print_struct_converters(
+    out: &mut Output,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    node_id: u64,
+    name: &str,
+    has_non_union_fields: bool,
+) -> Result<()> {
+    let node = get_node(node_map, node_id)?;
+    let has_union = get_struct_node(node)?.get_discriminant_count() > 0;
+    let type_ = format!(
+        "{}{}",
+        type_args(param_env),
+        escape_keyword(name.to_string())
+    );
+    // The type variables of the explicitly polymorphic converters below are
+    // not in scope in their bodies.
+    let body_type = match param_env.type_parameters().len() {
+        0 => type_.clone(),
+        1 => format!("_ {}", escape_keyword(name.to_string())),
+        n => format!(
+            "({}) {}",
+            vec!["_"; n].join(", "),
+            escape_keyword(name.to_string())
+        ),
+    };
+    for format in out.options.formats.clone() {
+        let module = format.module();
+        let value_type = format.value_type();
+        let members = to_members(
+            &out.options,
+            node_map,
+            node_name_map,
+            param_env,
+            format,
+            node_id,
+            "v",
+            if has_non_union_fields { "v.union" } else { "v" },
+        )?;
+        let mut value = if has_union {
+            union_of_format(
+                &out.options,
+                node_map,
+                node_name_map,
+                param_env,
+                format,
+                node,
+                "j",
+            )?
+        } else {
+            String::new()
+        };
+        if has_non_union_fields {
+            let mut fields = of_fields(
+                &out.options,
+                node_map,
+                node_name_map,
+                param_env,
+                format,
+                node_id,
+                "j",
+            )?;
+            if has_union {
+                fields.push(format!("union = {}", value));
+            }
+            value = format!("{{\n      {}\n    }}", fields.join(";\n      "));
+        }
+        let mut type_vars = String::new();
+        let mut to_param_types = String::new();
+        let mut of_param_types = String::new();
+        for param in param_env.type_parameters() {
+            write!(type_vars, " '{}", param).unwrap();
+            write!(to_param_types, "('{} -> {}) -> ", param, value_type).unwrap();
+            write!(
+                of_param_types,
+                "({} -> {}) -> ",
+                value_type,
+                format.result_type(&format!("'{}", param))
+            )
+            .unwrap();
+        }
+        let to_function = format.to_function(name);
+        let of_function = format.of_function(name);
+        // Like the decoders, the converters of generic types are explicitly
+        // polymorphic, so that other converters in the chain can use them at
+        // different types.
+        print_converter_separator(out);
+        if param_env.type_parameters().is_empty() {
+            writeln!(
+                out.converters,
+                "{} (v: {}): {} =",
+                to_function, type_, value_type
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                out.converters,
+                "{}:{}. {}{} -> {} = fun{} v ->",
+                to_function,
+                type_vars,
+                to_param_types,
+                type_,
+                value_type,
+                converter_args(param_env, |name| format.to_function(name))
+            )
+            .unwrap();
+        }
+        write!(
+            out.converters,
+            "  {}.obj [\n    {}\n  ]",
+            module,
+            members.join(";\n    ")
+        )
+        .unwrap();
+        print_converter_separator(out);
+        if param_env.type_parameters().is_empty() {
+            writeln!(
+                out.converters,
+                "{} (j: {}): {} =",
+                of_function,
+                value_type,
+                format.result_type(&type_)
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                out.converters,
+                "{}:{}. {}{} -> {} = fun{} j ->",
+                of_function,
+                type_vars,
+                of_param_types,
+                value_type,
+                format.result_type(&type_),
+                converter_args(param_env, |name| format.of_function(name))
+            )
+            .unwrap();
+        }
+        write!(
+            out.converters,
+            "  {}.wrap (fun () : {} ->\n    {})",
+            module, body_type, value
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val {}: {}{} -> {}",
+            to_function, to_param_types, type_, value_type
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val {}: {}{} -> {}",
+            of_function,
+            of_param_types,
+            value_type,
+            format.result_type(&type_)
+        )
+        .unwrap();
+    }
+    Ok(())
+}
+
+/// Prints the converters of enum `enum_`, whose type is `name`, to and from
+/// each format of `--json` and `--sexp`, and adds their signatures to the
+/// interface. Enumerants are represented by their names, and unknown ones by
+/// their numbers.
+pub(crate) fn print_enum_converters(
+    out: &mut Output,
+    enum_: schema_capnp::node::enum_::Reader,
+    name: &str,
+) -> Result<()> {
+    let escaped_name = escape_keyword(name.to_string());
+    for format in out.options.formats.clone() {
+        let module = format.module();
+        let value_type = format.value_type();
+        let mut to_cases = String::new();
+        let mut of_cases = String::new();
+        for (index, enumerant) in enum_.get_enumerants()?.iter().enumerate() {
+            let enumerant_name = enumerant.get_name()?.to_str()?;
+            let constructor = constructor_name(enumerant.get_annotations()?, enumerant_name)?;
+            write!(
+                to_cases,
+                "\n  | {} -> {}.enumerant \"{}\"",
+                constructor, module, enumerant_name
+            )
+            .unwrap();
+            write!(
+                of_cases,
+                "\n    | `Name \"{}\" | `Ordinal {} -> {}",
+                enumerant_name, index, constructor
+            )
+            .unwrap();
+        }
+        if out.options.unknown {
+            write!(to_cases, "\n  | Unknown n -> {}.int n", module).unwrap();
+            write!(of_cases, "\n    | `Ordinal n -> Unknown n").unwrap();
+        }
+        write!(
+            of_cases,
+            "\n    | _ -> {}.fail \"undefined enumerant\" j",
+            module
+        )
+        .unwrap();
+        print_converter_separator(out);
+        write!(
+            out.converters,
+            "{} (v: {}): {} = match v with{}",
+            format.to_function(name),
+            escaped_name,
+            value_type,
+            to_cases
+        )
+        .unwrap();
+        print_converter_separator(out);
+        write!(
+            out.converters,
+            "{} (j: {}): {} =\n  {}.wrap (fun () : {} ->\n    match {}.to_enumerant j with{})",
+            format.of_function(name),
+            value_type,
+            format.result_type(&escaped_name),
+            module,
+            escaped_name,
+            module,
+            of_cases
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val {}: {} -> {}",
+            format.to_function(name),
+            escaped_name,
+            value_type
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val {}: {} -> {}",
+            format.of_function(name),
+            value_type,
+            format.result_type(&escaped_name)
+        )
+        .unwrap();
+    }
+    Ok(())
+}
+
+/// The helper functions used by the converters to and from JSON, which follow
+/// the JSON mapping of Cap'n Proto: 64-bit integers and non-finite floats are
+/// strings, `Data` is an array of bytes, and `Void` and capabilities are null.
+const JSON_HELPERS: &str = include_str!("ocaml/json_helpers.ml");
+
+/// The helper functions used by the converters to and from S-expressions,
+/// which map values as in JSON, with `()` for null and `((name value) ...)` for
+/// objects.
+const SEXP_HELPERS: &str = include_str!("ocaml/sexp_helpers.ml");
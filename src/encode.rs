@@ -0,0 +1,426 @@
+//! The printers of the encoders, which store OCaml values into
+//! capnp-ocaml builders.
+
+use super::*;
+
+fn print_type_encoder(
+    encoder: &mut String,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader<'_>,
+) -> Result<()> {
+    match type_.which()? {
+        schema_capnp::type_::Struct(struct_) => {
+            let type_id = struct_.get_type_id();
+            let encoder_name = function_name(node_name_map, "encode", type_id)?;
+            write!(encoder, "(fun p v -> {}", encoder_name).unwrap();
+            print_brand_encoders(encoder, node_map, node_name_map, param_env, struct_)?;
+            let node = get_node(node_map, type_id)?;
+            write!(
+                encoder,
+                " ({}.init_pointer p) v)",
+                builder_module_path(node_name_map, node)?
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which()? {
+            schema_capnp::type_::any_pointer::Parameter(parameter) => {
+                write!(
+                    encoder,
+                    "encode_{}",
+                    parameter_name(
+                        param_env,
+                        parameter.get_scope_id(),
+                        parameter.get_parameter_index()
+                    )?
+                )
+                .unwrap();
+            }
+            schema_capnp::type_::any_pointer::Unconstrained(_) => {
+                return Err(Error::new(UNTYPED_ENCODING_ERROR))
+            }
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                return Err(Error::new("implicit method parameters are not supported"))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                "this type is not supported as a generic argument",
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn print_brand_encoders(
+    encoder: &mut String,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    struct_: schema_capnp::type_::struct_::Reader<'_>,
+) -> Result<()> {
+    for argument in brand_arguments(node_map, struct_)? {
+        write!(encoder, " ").unwrap();
+        match argument {
+            BrandArgument::Type(type_) => {
+                print_type_encoder(encoder, node_map, node_name_map, param_env, type_)?
+            }
+            BrandArgument::Inherited(scope_id, index) => write!(
+                encoder,
+                "encode_{}",
+                parameter_name(param_env, scope_id, index)?
+            )
+            .unwrap(),
+            BrandArgument::Unbound => return Err(Error::new(UNTYPED_ENCODING_ERROR)),
+        }
+    }
+    Ok(())
+}
+
+/// Writes an OCaml expression that stores `value`, a list or array (depending
+/// on `repr`) of elements of type `element_type`, into list field
+/// `field_name` of the builder `builder` of struct module `builder_path`.
+#[allow(clippy::too_many_arguments)]
+fn print_list_encoding(
+    encoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    element_type: schema_capnp::type_::Reader<'_>,
+    builder_path: &str,
+    builder: &str,
+    field_name: &str,
+    value: &str,
+    repr: ListRepr,
+) -> Result<()> {
+    let module = repr.module_name();
+    write!(
+        encoder,
+        "(let a = {}.{}_init {} ({}.length {}) in {}.iteri (fun i v' -> ",
+        builder_path, field_name, builder, module, value, module
+    )
+    .unwrap();
+    print_element_encoding(
+        encoder,
+        options,
+        node_map,
+        node_name_map,
+        param_env,
+        element_type,
+    )?;
+    write!(encoder, ") {})", value).unwrap();
+    Ok(())
+}
+
+/// Writes an OCaml expression that stores element `v'` of type `element_type`
+/// at index `i` of list builder `a`. The elements of a nested list are stored
+/// the same way, with the names shadowed.
+fn print_element_encoding(
+    encoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    element_type: schema_capnp::type_::Reader<'_>,
+) -> Result<()> {
+    match element_type.which()? {
+        schema_capnp::type_::Data(()) if options.data_repr.encoder().is_some() => {
+            write!(
+                encoder,
+                "Capnp.Array.set a i ({} v')",
+                options.data_repr.encoder().unwrap()
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::Void(())
+        | schema_capnp::type_::Bool(())
+        | schema_capnp::type_::Int8(())
+        | schema_capnp::type_::Int16(())
+        | schema_capnp::type_::Uint8(())
+        | schema_capnp::type_::Uint16(())
+        | schema_capnp::type_::Float32(())
+        | schema_capnp::type_::Float64(())
+        | schema_capnp::type_::Text(())
+        | schema_capnp::type_::Data(())
+        | schema_capnp::type_::Interface(_) => {
+            write!(encoder, "Capnp.Array.set a i v'").unwrap();
+        }
+        schema_capnp::type_::Int32(())
+        | schema_capnp::type_::Int64(())
+        | schema_capnp::type_::Uint32(())
+        | schema_capnp::type_::Uint64(()) => {
+            let int_repr = IntRepr::of_type(element_type)?.expect("an integer type");
+            write!(
+                encoder,
+                "Capnp.Array.set a i {}",
+                int_repr.encoding(options.int_repr(int_repr), "v'")
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::List(list) => {
+            // `init` allocates a new inner list, so the list is read from the
+            // outer one again rather than through the handle read before.
+            let module = options.list_repr.module_name();
+            write!(
+                encoder,
+                "let a = (Capnp.Array.init (Capnp.Array.get a i) ({}.length v'); Capnp.Array.get a i) in {}.iteri (fun i v' -> ",
+                module, module
+            )
+            .unwrap();
+            print_element_encoding(
+                encoder,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+            )?;
+            write!(encoder, ") v'").unwrap();
+        }
+        schema_capnp::type_::Enum(enum_) => {
+            let encoder_name = function_name(node_name_map, "encode", enum_.get_type_id())?;
+            if options.unknown {
+                // Lists have no counterpart of the fields' `_set_unsafe`
+                // setter, so an `Undefined` element cannot be written.
+                write!(
+                    encoder,
+                    "Capnp.Array.set a i (match (v': {}) with \
+                     Unknown _ -> invalid_arg \"Unknown enumerant in a list\" \
+                     | v' -> {} v')",
+                    type_name(node_name_map, enum_.get_type_id())?,
+                    encoder_name
+                )
+                .unwrap();
+            } else {
+                write!(encoder, "Capnp.Array.set a i ({} v')", encoder_name).unwrap();
+            }
+        }
+        schema_capnp::type_::Struct(struct_) => {
+            write!(
+                encoder,
+                "{}",
+                function_name(node_name_map, "encode", struct_.get_type_id())?
+            )
+            .unwrap();
+            print_brand_encoders(encoder, node_map, node_name_map, param_env, struct_)?;
+            write!(encoder, " (Capnp.Array.get a i) v'").unwrap();
+        }
+        schema_capnp::type_::AnyPointer(_) => {
+            print_type_encoder(encoder, node_map, node_name_map, param_env, element_type)?;
+            write!(encoder, " (Capnp.Array.get a i) v'").unwrap();
+        }
+    }
+    Ok(())
+}
+
+/// Writes an OCaml expression that stores `value` into field `field_name` of
+/// the builder `builder` of struct module `builder_path`.
+#[allow(clippy::too_many_arguments)]
+fn print_type_encoding(
+    encoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader<'_>,
+    builder_path: &str,
+    builder: &str,
+    field_name: &str,
+    value: &str,
+) -> Result<()> {
+    match type_.which()? {
+        schema_capnp::type_::Void(()) => {
+            write!(encoder, "()").unwrap();
+        }
+        schema_capnp::type_::Int8(())
+        | schema_capnp::type_::Int16(())
+        | schema_capnp::type_::Uint8(())
+        | schema_capnp::type_::Uint16(()) => {
+            write!(
+                encoder,
+                "{}.{}_set_exn {} {}",
+                builder_path, field_name, builder, value
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::Int32(())
+        | schema_capnp::type_::Int64(())
+        | schema_capnp::type_::Uint32(())
+        | schema_capnp::type_::Uint64(()) => {
+            let int_repr = IntRepr::of_type(type_)?.expect("an integer type");
+            write!(
+                encoder,
+                "{}.{}_set {} {}",
+                builder_path,
+                field_name,
+                builder,
+                int_repr.encoding(options.int_repr(int_repr), value)
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::Data(()) if options.data_repr.encoder().is_some() => {
+            write!(
+                encoder,
+                "{}.{}_set {} ({} {})",
+                builder_path,
+                field_name,
+                builder,
+                options.data_repr.encoder().unwrap(),
+                value
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::Bool(())
+        | schema_capnp::type_::Float32(())
+        | schema_capnp::type_::Float64(())
+        | schema_capnp::type_::Text(())
+        | schema_capnp::type_::Data(())
+        | schema_capnp::type_::Interface(_) => {
+            write!(
+                encoder,
+                "{}.{}_set {} {}",
+                builder_path, field_name, builder, value
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::List(list) => {
+            print_list_encoding(
+                encoder,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+                builder_path,
+                builder,
+                field_name,
+                value,
+                options.list_repr,
+            )?;
+        }
+        schema_capnp::type_::Enum(enum_) => {
+            // Only the `_set_unsafe` setter accepts `Undefined` enumerants.
+            let setter = if options.unknown { "set_unsafe" } else { "set" };
+            write!(
+                encoder,
+                "{}.{}_{} {} ({} {})",
+                builder_path,
+                field_name,
+                setter,
+                builder,
+                function_name(node_name_map, "encode", enum_.get_type_id())?,
+                value
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::Struct(struct_) => {
+            write!(
+                encoder,
+                "{}",
+                function_name(node_name_map, "encode", struct_.get_type_id())?
+            )
+            .unwrap();
+            print_brand_encoders(encoder, node_map, node_name_map, param_env, struct_)?;
+            write!(
+                encoder,
+                " ({}.{}_init {}) {}",
+                builder_path, field_name, builder, value
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which()? {
+            schema_capnp::type_::any_pointer::Parameter(parameter) => {
+                write!(
+                    encoder,
+                    "encode_{} ({}.{}_get {}) {}",
+                    parameter_name(
+                        param_env,
+                        parameter.get_scope_id(),
+                        parameter.get_parameter_index()
+                    )?,
+                    builder_path,
+                    field_name,
+                    builder,
+                    value
+                )
+                .unwrap();
+            }
+            schema_capnp::type_::any_pointer::Unconstrained(unconstrained) => {
+                let kind = PointerKind::of_unconstrained(unconstrained)?;
+                write!(
+                    encoder,
+                    "ignore ({}.{}_set_reader {} {})",
+                    builder_path,
+                    field_name,
+                    builder,
+                    kind.unwrap(value)
+                )
+                .unwrap();
+            }
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                return Err(Error::new("implicit method parameters are not supported"))
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Like `print_type_encoding`, for a field represented as `repr`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn print_field_type_encoding(
+    encoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader<'_>,
+    builder_path: &str,
+    builder: &str,
+    field_name: &str,
+    value: &str,
+    repr: FieldRepr,
+) -> Result<()> {
+    match type_.which()? {
+        schema_capnp::type_::List(list) => print_list_encoding(
+            encoder,
+            options,
+            node_map,
+            node_name_map,
+            param_env,
+            list.get_element_type()?,
+            builder_path,
+            builder,
+            field_name,
+            value,
+            repr.list(options),
+        ),
+        _ => match (IntRepr::of_type(type_)?, repr) {
+            (Some(int_repr), FieldRepr::Int(repr)) => {
+                write!(
+                    encoder,
+                    "{}.{}_set {} {}",
+                    builder_path,
+                    field_name,
+                    builder,
+                    int_repr.encoding(repr, value)
+                )
+                .unwrap();
+                Ok(())
+            }
+            _ => print_type_encoding(
+                encoder,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                type_,
+                builder_path,
+                builder,
+                field_name,
+                value,
+            ),
+        },
+    }
+}
@@ -0,0 +1,361 @@
+//! The printers of OCaml literals for the constants and default values
+//! declared in the schema.
+
+use super::*;
+
+/// Returns an OCaml string literal holding `bytes`.
+pub(crate) fn string_literal(bytes: &[u8]) -> String {
+    let mut literal = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b' '..=b'~' => literal.push(byte as char),
+            _ => write!(literal, "\\x{:02x}", byte).unwrap(),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Returns an OCaml expression for float `x`.
+pub(crate) fn float_literal(x: f64) -> String {
+    if x.is_nan() {
+        "Float.nan".to_string()
+    } else if x == f64::INFINITY {
+        "Float.infinity".to_string()
+    } else if x == f64::NEG_INFINITY {
+        "Float.neg_infinity".to_string()
+    } else {
+        // Debug formatting always includes a `.` or an exponent, as OCaml
+        // float literals require.
+        format!("{:?}", x)
+    }
+}
+
+/// Returns an OCaml expression for a message whose root is `value`, for
+/// capnp-ocaml readers to decode.
+fn message_literal<T: capnp::traits::Owned>(
+    value: impl capnp::traits::SetterInput<T>,
+) -> Result<String> {
+    let mut message = capnp::message::Builder::new_default();
+    message.set_root(value)?;
+    let segments = message.get_segments_for_output();
+    let segments: Vec<String> = segments
+        .iter()
+        .map(|segment| format!("Bytes.of_string {}", string_literal(segment)))
+        .collect();
+    Ok(format!(
+        "Capnp.BytesMessage.Message.of_storage [{}]",
+        segments.join("; ")
+    ))
+}
+
+/// Returns the constructor for enumerant `value` of enum `id`.
+fn enumerant_literal(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    id: u64,
+    value: u16,
+) -> Result<String> {
+    let schema_capnp::node::Enum(enum_) = get_node(node_map, id)?.which()? else {
+        return Err(Error::new(format!("node @0x{:x} is not an enum", id)));
+    };
+    let enumerants = enum_.get_enumerants()?;
+    if u32::from(value) >= enumerants.len() {
+        if options.unknown {
+            return Ok(format!("(Unknown {})", value));
+        }
+        return Err(Error::new(format!(
+            "enumerant {} is not in the schema",
+            value
+        )));
+    }
+    let enumerant = enumerants.get(value.into());
+    let name = constructor_name(
+        enumerant.get_annotations()?,
+        enumerant.get_name()?.to_str()?,
+    )?;
+    // Constructors of imported enums are qualified by their decoder module.
+    match type_name(node_name_map, id)?.rsplit_once('.') {
+        Some((module, _)) => Ok(format!("{}.{}", module, name)),
+        None => Ok(name),
+    }
+}
+
+/// Returns an OCaml expression that decodes struct `value` of type `type_`.
+fn struct_literal(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    type_: schema_capnp::type_::Reader,
+    message: &str,
+) -> Result<String> {
+    let schema_capnp::type_::Struct(struct_) = type_.which()? else {
+        return Err(Error::new("expected a struct type"));
+    };
+    let reader_path =
+        reader_module_path(node_name_map, get_node(node_map, struct_.get_type_id())?)?;
+    let mut decoding = String::new();
+    print_type_decoding(
+        &mut decoding,
+        options,
+        node_map,
+        node_name_map,
+        None,
+        type_,
+        &format!("({}.of_message ({}))", reader_path, message),
+    )?;
+    if options.result {
+        Ok(format!("Result.get_ok ({})", decoding))
+    } else {
+        Ok(decoding)
+    }
+}
+
+/// Returns the OCaml literals for the elements of primitive list `list`.
+fn primitive_literals<'a, T: capnp::private::layout::PrimitiveElement>(
+    list: capnp::any_pointer::Reader<'a>,
+    literal: impl Fn(T) -> String,
+) -> Result<Vec<String>> {
+    let list: capnp::primitive_list::Reader<'a, T> = list.get_as()?;
+    Ok(list.iter().map(literal).collect())
+}
+
+/// Like `primitive_literals`, for a list of integers represented as `repr`.
+fn int_literals<'a, T: capnp::private::layout::PrimitiveElement + Into<i128>>(
+    list: capnp::any_pointer::Reader<'a>,
+    repr: IntRepr,
+) -> Result<Vec<String>> {
+    let list: capnp::primitive_list::Reader<'a, T> = list.get_as()?;
+    list.iter().map(|x| repr.literal(x.into())).collect()
+}
+
+/// Returns an OCaml expression for list `list` with elements of type
+/// `element_type`, represented as `repr`.
+pub(crate) fn list_literal(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    element_type: schema_capnp::type_::Reader,
+    list: capnp::any_pointer::Reader,
+    repr: ListRepr,
+) -> Result<String> {
+    let elements = match element_type.which()? {
+        schema_capnp::type_::Void(()) => primitive_literals(list, |()| "()".to_string())?,
+        schema_capnp::type_::Bool(()) => primitive_literals(list, |x: bool| x.to_string())?,
+        schema_capnp::type_::Int8(()) => primitive_literals(list, |x: i8| x.to_string())?,
+        schema_capnp::type_::Int16(()) => primitive_literals(list, |x: i16| x.to_string())?,
+        schema_capnp::type_::Int32(()) => {
+            int_literals::<i32>(list, options.int_repr(IntRepr::Int32))?
+        }
+        schema_capnp::type_::Int64(()) => {
+            int_literals::<i64>(list, options.int_repr(IntRepr::Int64))?
+        }
+        schema_capnp::type_::Uint8(()) => primitive_literals(list, |x: u8| x.to_string())?,
+        schema_capnp::type_::Uint16(()) => primitive_literals(list, |x: u16| x.to_string())?,
+        schema_capnp::type_::Uint32(()) => {
+            int_literals::<u32>(list, options.int_repr(IntRepr::Uint32))?
+        }
+        schema_capnp::type_::Uint64(()) => {
+            int_literals::<u64>(list, options.int_repr(IntRepr::Uint64))?
+        }
+        schema_capnp::type_::Float32(()) => {
+            primitive_literals(list, |x: f32| float_literal(x.into()))?
+        }
+        schema_capnp::type_::Float64(()) => primitive_literals(list, float_literal)?,
+        schema_capnp::type_::Text(()) => {
+            let list: capnp::text_list::Reader = list.get_as()?;
+            let mut elements = Vec::new();
+            for text in list.iter() {
+                elements.push(string_literal(text?.as_bytes()));
+            }
+            elements
+        }
+        schema_capnp::type_::Data(()) => {
+            let list: capnp::data_list::Reader = list.get_as()?;
+            let mut elements = Vec::new();
+            for data in list.iter() {
+                elements.push(options.data_repr.literal(data?));
+            }
+            elements
+        }
+        schema_capnp::type_::Enum(enum_) => {
+            let list: capnp::primitive_list::Reader<u16> = list.get_as()?;
+            let mut elements = Vec::new();
+            for value in list.iter() {
+                elements.push(enumerant_literal(
+                    options,
+                    node_map,
+                    node_name_map,
+                    enum_.get_type_id(),
+                    value,
+                )?);
+            }
+            elements
+        }
+        schema_capnp::type_::List(list_type) => {
+            let list: capnp::any_pointer_list::Reader = list.get_as()?;
+            let mut elements = Vec::new();
+            for element in list.iter() {
+                elements.push(list_literal(
+                    options,
+                    node_map,
+                    node_name_map,
+                    list_type.get_element_type()?,
+                    element?,
+                    options.list_repr,
+                )?);
+            }
+            elements
+        }
+        schema_capnp::type_::Struct(_) => {
+            // capnp offers no untyped reader for lists of structs, so read the
+            // elements as nodes; copying an element into a message of its own
+            // copies its sections as they are, whatever its actual type.
+            let list: capnp::struct_list::Reader<schema_capnp::node::Owned> = list.get_as()?;
+            let mut elements = Vec::new();
+            for element in list.iter() {
+                elements.push(struct_literal(
+                    options,
+                    node_map,
+                    node_name_map,
+                    element_type,
+                    &message_literal(element)?,
+                )?);
+            }
+            elements
+        }
+        schema_capnp::type_::Interface(_) => {
+            let list: capnp::any_pointer_list::Reader = list.get_as()?;
+            vec!["None".to_string(); list.len() as usize]
+        }
+        schema_capnp::type_::AnyPointer(_) => {
+            return Err(Error::new(
+                "constants of AnyPointer lists are not supported",
+            ))
+        }
+    };
+    let elements = elements.join("; ");
+    Ok(match repr {
+        ListRepr::List => format!("[{}]", elements),
+        ListRepr::Array => format!("[|{}|]", elements),
+        ListRepr::Seq => format!("List.to_seq [{}]", elements),
+    })
+}
+
+/// Returns an OCaml expression for constant value `value` of type `type_`.
+pub(crate) fn constant_literal(
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    type_: schema_capnp::type_::Reader,
+    value: schema_capnp::value::Reader,
+) -> Result<String> {
+    Ok(match value.which()? {
+        schema_capnp::value::Void(()) => "()".to_string(),
+        schema_capnp::value::Bool(x) => x.to_string(),
+        schema_capnp::value::Int8(x) => x.to_string(),
+        schema_capnp::value::Int16(x) => x.to_string(),
+        schema_capnp::value::Int32(x) => options.int_repr(IntRepr::Int32).literal(x.into())?,
+        schema_capnp::value::Int64(x) => options.int_repr(IntRepr::Int64).literal(x.into())?,
+        schema_capnp::value::Uint8(x) => x.to_string(),
+        schema_capnp::value::Uint16(x) => x.to_string(),
+        schema_capnp::value::Uint32(x) => options.int_repr(IntRepr::Uint32).literal(x.into())?,
+        schema_capnp::value::Uint64(x) => options.int_repr(IntRepr::Uint64).literal(x.into())?,
+        schema_capnp::value::Float32(x) => float_literal(x.into()),
+        schema_capnp::value::Float64(x) => float_literal(x),
+        schema_capnp::value::Text(x) => string_literal(x?.as_bytes()),
+        schema_capnp::value::Data(x) => options.data_repr.literal(x?),
+        schema_capnp::value::List(list) => {
+            let schema_capnp::type_::List(list_type) = type_.which()? else {
+                return Err(Error::new("expected a list type"));
+            };
+            list_literal(
+                options,
+                node_map,
+                node_name_map,
+                list_type.get_element_type()?,
+                list,
+                options.list_repr,
+            )?
+        }
+        schema_capnp::value::Enum(x) => {
+            let schema_capnp::type_::Enum(enum_) = type_.which()? else {
+                return Err(Error::new("expected an enum type"));
+            };
+            enumerant_literal(options, node_map, node_name_map, enum_.get_type_id(), x)?
+        }
+        schema_capnp::value::Struct(x) => struct_literal(
+            options,
+            node_map,
+            node_name_map,
+            type_,
+            &message_literal(x)?,
+        )?,
+        // Capabilities cannot be written in a schema, so this is null.
+        schema_capnp::value::Interface(()) => "None".to_string(),
+        schema_capnp::value::AnyPointer(_) => {
+            return Err(Error::new("AnyPointer constants are not supported"))
+        }
+    })
+}
+
+/// Binds a message whose root is a struct without data and pointers, which
+/// capnp-ocaml readers present as a struct whose fields all have their default
+/// values.
+pub(crate) const EMPTY_MESSAGE: &str = "\
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string \"\\xfc\\xff\\xff\\xff\\x00\\x00\\x00\\x00\"]
+";
+
+/// Prints `default_<name>`, the value of struct `name` whose fields all have
+/// their schema defaults, obtained by decoding an empty struct. Like the
+/// decoder, it takes decoders for the struct's generic parameters.
+pub(crate) fn print_default(
+    out: &mut Output,
+    param_env: &ParamEnv<'_>,
+    name: &str,
+    reader_path: &str,
+) {
+    let type_ = format!(
+        "{}{}",
+        type_args(param_env),
+        escape_keyword(name.to_string())
+    );
+    let mut decoding = format!(
+        "decode_{}{} ({}.of_message empty_message)",
+        name,
+        fun_args(param_env, "decode"),
+        reader_path
+    );
+    if out.options.result {
+        decoding = format!("Result.get_ok ({})", decoding);
+    }
+    if param_env.type_parameters().is_empty() {
+        writeln!(
+            out.defaults,
+            "let default_{}: {} = {}",
+            name, type_, decoding
+        )
+        .unwrap();
+    } else {
+        writeln!(
+            out.defaults,
+            "let default_{}{} = {}",
+            name,
+            fun_args(param_env, "decode"),
+            decoding
+        )
+        .unwrap();
+    }
+    writeln!(
+        out.interface,
+        "val default_{}: {}{}",
+        name,
+        decoder_param_types(&out.options, param_env),
+        type_
+    )
+    .unwrap();
+}
@@ -1,10 +1,18 @@
-use std::collections::HashMap;
+mod convert;
+mod encode;
+mod literal;
+
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::Write;
 
 use capnp::schema_capnp;
 use capnp::schema_capnp::code_generator_request::requested_file;
 use capnp::schema_capnp::field::NO_DISCRIMINANT;
 
+use convert::{print_enum_converters, print_struct_converters, Format};
+use encode::print_field_type_encoding;
+use literal::{constant_literal, list_literal, print_default, string_literal, EMPTY_MESSAGE};
+
 const OCAML_KEYWORDS: [&str; 56] = [
     "and",
     "as",
@@ -74,6 +82,332 @@ lazy_static::lazy_static! {
     };
 }
 
+/// The IDs of the annotations declared by `ocaml_decoder.capnp`.
+const NAME_ANNOTATION_ID: u64 = 0xd6b1a4c7e2f38059;
+const REPR_ANNOTATION_ID: u64 = 0xe48c2f9a7b1d6035;
+const SKIP_ANNOTATION_ID: u64 = 0xb93e5d0c8a7f2146;
+const INLINE_ANNOTATION_ID: u64 = 0xc17a8e3b5d2f9064;
+const OPTIONS_ANNOTATION_ID: u64 = 0xa58d3f6e1c94b027;
+
+/// An error that prevents generating code for a schema, together with the
+/// schema element it concerns.
+///
+/// The element is identified by its fully-qualified name only: the
+/// `sourceInfo` that capnp 0.20 puts in the request carries doc comments but
+/// no byte offsets, so there is no line or column to report.
+struct Error {
+    message: String,
+    /// The display name of the node the error concerns (e.g.
+    /// `example.capnp:Expr`).
+    node: Option<String>,
+    /// The name of the member (field or enumerant) of `node` the error
+    /// concerns.
+    member: Option<String>,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    fn new(message: impl Into<String>) -> Error {
+        Error {
+            message: message.into(),
+            node: None,
+            member: None,
+        }
+    }
+
+    /// Attributes the error to `node`, unless it has been attributed to a
+    /// node already.
+    fn in_node(mut self, node: schema_capnp::node::Reader) -> Error {
+        if self.node.is_none() {
+            self.node = node
+                .get_display_name()
+                .ok()
+                .and_then(|name| name.to_string().ok());
+        }
+        self
+    }
+
+    /// Attributes the error to member `name` of the node it will be attributed
+    /// to, unless it has been attributed to a member or node already.
+    fn in_member(mut self, name: &str) -> Error {
+        if self.node.is_none() && self.member.is_none() {
+            self.member = Some(name.to_string());
+        }
+        self
+    }
+
+    /// Prints the error in the format of the capnp compiler's diagnostics,
+    /// with the fully-qualified name of the element it concerns.
+    fn report(&self) {
+        let Some(node) = &self.node else {
+            eprintln!("error: {}", self.message);
+            return;
+        };
+        let (filename, path) = node.split_once(':').unwrap_or((node, ""));
+        let mut element: Vec<&str> = path.split('.').filter(|name| !name.is_empty()).collect();
+        element.extend(self.member.as_deref());
+        if element.is_empty() {
+            eprintln!("{}: error: {}", filename, self.message);
+        } else {
+            eprintln!(
+                "{}: error: {}: {}",
+                filename,
+                element.join("."),
+                self.message
+            );
+        }
+    }
+}
+
+impl From<capnp::Error> for Error {
+    fn from(error: capnp::Error) -> Error {
+        Error::new(error.to_string())
+    }
+}
+
+impl From<capnp::NotInSchema> for Error {
+    fn from(error: capnp::NotInSchema) -> Error {
+        Error::new(error.to_string())
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(error: std::str::Utf8Error) -> Error {
+        Error::new(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::new(error.to_string())
+    }
+}
+
+/// Returns the node with id `id`.
+fn get_node<'a>(
+    node_map: &HashMap<u64, schema_capnp::node::Reader<'a>>,
+    id: u64,
+) -> Result<schema_capnp::node::Reader<'a>> {
+    node_map
+        .get(&id)
+        .copied()
+        .ok_or_else(|| Error::new(format!("unknown node @0x{:x}", id)))
+}
+
+/// Returns the struct (or group) part of `node`.
+fn get_struct_node(
+    node: schema_capnp::node::Reader,
+) -> Result<schema_capnp::node::struct_::Reader> {
+    match node.which()? {
+        schema_capnp::node::Struct(struct_node) => Ok(struct_node),
+        _ => Err(Error::new("expected a struct").in_node(node)),
+    }
+}
+
+/// Returns the value of annotation `id` among `annotations`, if present.
+fn find_annotation(
+    annotations: capnp::struct_list::Reader<'_, schema_capnp::annotation::Owned>,
+    id: u64,
+) -> Result<Option<schema_capnp::value::Reader<'_>>> {
+    for annotation in annotations.iter() {
+        if annotation.get_id() == id {
+            return Ok(Some(annotation.get_value()?));
+        }
+    }
+    Ok(None)
+}
+
+fn has_annotation(
+    annotations: capnp::struct_list::Reader<'_, schema_capnp::annotation::Owned>,
+    id: u64,
+) -> Result<bool> {
+    Ok(find_annotation(annotations, id)?.is_some())
+}
+
+/// Returns the value of text annotation `id` among `annotations`, if present.
+fn text_annotation(
+    annotations: capnp::struct_list::Reader<'_, schema_capnp::annotation::Owned>,
+    id: u64,
+) -> Result<Option<&str>> {
+    match find_annotation(annotations, id)? {
+        Some(value) => match value.which()? {
+            schema_capnp::value::Text(text) => Ok(Some(text?.to_str()?)),
+            _ => Err(Error::new(format!("annotation @0x{:x} must be a Text", id))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns the `$OCaml.name` among `annotations`, if present, after checking
+/// that it is a valid OCaml name: a constructor if `is_constructor` is set,
+/// and otherwise a lowercase identifier that is not a keyword.
+fn name_annotation(
+    annotations: capnp::struct_list::Reader<'_, schema_capnp::annotation::Owned>,
+    is_constructor: bool,
+) -> Result<Option<&str>> {
+    let Some(name) = text_annotation(annotations, NAME_ANNOTATION_ID)? else {
+        return Ok(None);
+    };
+    let mut chars = name.chars();
+    let is_valid_start = match chars.next() {
+        Some(first) if is_constructor => first.is_ascii_uppercase(),
+        Some(first) => {
+            (first.is_ascii_lowercase() || first == '_')
+                && name != "_"
+                && !OCAML_KEYWORDS_SET.contains(name)
+        }
+        None => false,
+    };
+    if !is_valid_start || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '\'') {
+        return Err(Error::new(format!(
+            "$OCaml.name(\"{}\") is not a valid OCaml {}",
+            name,
+            if is_constructor {
+                "constructor name"
+            } else {
+                "lowercase identifier"
+            }
+        )));
+    }
+    Ok(Some(name))
+}
+
+/// Returns the `$OCaml.name` of group field `field`, which capnp attaches
+/// either to the field or to the group node, checked like `name_annotation`.
+fn group_name_annotation<'a>(
+    node_map: &HashMap<u64, schema_capnp::node::Reader<'a>>,
+    field: schema_capnp::field::Reader<'a>,
+    group: schema_capnp::field::group::Reader<'a>,
+    is_constructor: bool,
+) -> Result<Option<&'a str>> {
+    let group_node = get_node(node_map, group.get_type_id())?;
+    match name_annotation(group_node.get_annotations()?, is_constructor)? {
+        Some(name) => Ok(Some(name)),
+        None => name_annotation(field.get_annotations()?, is_constructor),
+    }
+}
+
+/// Returns the OCaml record field name of `field`: its `$OCaml.name`, or else
+/// its name in snake case.
+fn field_label(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    field: schema_capnp::field::Reader,
+) -> Result<String> {
+    let name = match field.which()? {
+        schema_capnp::field::Group(group) => group_name_annotation(node_map, field, group, false)?,
+        schema_capnp::field::Slot(_) => name_annotation(field.get_annotations()?, false)?,
+    };
+    match name {
+        Some(name) => Ok(name.to_string()),
+        None => Ok(escape_keyword(pascal_to_snake(field.get_name()?.to_str()?))),
+    }
+}
+
+/// Returns the OCaml constructor name of the union member or enumerant `name`
+/// with annotations `annotations`: its `$OCaml.name`, or else its name,
+/// capitalized. This differs from the constructor generated by capnp-ocaml,
+/// which is always the latter.
+fn constructor_name(
+    annotations: capnp::struct_list::Reader<'_, schema_capnp::annotation::Owned>,
+    name: &str,
+) -> Result<String> {
+    match name_annotation(annotations, true)? {
+        Some(name) => Ok(name.to_string()),
+        None => capitalize(name),
+    }
+}
+
+/// Returns the OCaml constructor name of union member `field`, as
+/// `constructor_name` does.
+fn member_constructor(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    field: schema_capnp::field::Reader,
+) -> Result<String> {
+    let name = field.get_name()?.to_str()?;
+    match field.which()? {
+        schema_capnp::field::Group(group) => {
+            match group_name_annotation(node_map, field, group, true)? {
+                Some(name) => Ok(name.to_string()),
+                None => capitalize(name),
+            }
+        }
+        schema_capnp::field::Slot(_) => constructor_name(field.get_annotations()?, name),
+    }
+}
+
+/// Returns the doc comment of node `id`, or an empty string if it has none.
+fn node_doc<'a>(
+    docs: &HashMap<u64, schema_capnp::node::source_info::Reader<'a>>,
+    id: u64,
+) -> Result<&'a str> {
+    match docs.get(&id) {
+        Some(source_info) => Ok(source_info.get_doc_comment()?.to_str()?),
+        None => Ok(""),
+    }
+}
+
+/// Returns the doc comment of the field or enumerant at `index` in node `id`,
+/// or an empty string if it has none.
+fn member_doc<'a>(
+    docs: &HashMap<u64, schema_capnp::node::source_info::Reader<'a>>,
+    id: u64,
+    index: u32,
+) -> Result<&'a str> {
+    let Some(source_info) = docs.get(&id) else {
+        return Ok("");
+    };
+    let members = source_info.get_members()?;
+    if index >= members.len() {
+        return Ok("");
+    }
+    Ok(members.get(index).get_doc_comment()?.to_str()?)
+}
+
+/// Returns `doc` as an OCaml doc comment whose continuation lines are indented
+/// past `indent`, or an empty string if `doc` is blank.
+fn doc_comment(doc: &str, indent: &str) -> String {
+    let doc = doc.trim();
+    if doc.is_empty() {
+        return String::new();
+    }
+    // OCaml comments nest, and the lexer reads the string literals inside
+    // them, so comment delimiters are broken up and unbalanced double quotes
+    // are replaced.
+    let mut doc = doc.replace("(*", "( *").replace("*)", "* )");
+    if doc.matches('"').count() % 2 == 1 {
+        doc = doc.replace('"', "''");
+    }
+    let mut comment = String::from("(** ");
+    for (i, line) in doc.lines().enumerate() {
+        let line = line.trim_end();
+        if i > 0 {
+            comment.push('\n');
+            if !line.is_empty() {
+                write!(comment, "{}    ", indent).unwrap();
+            }
+        }
+        comment.push_str(line);
+    }
+    comment.push_str(" *)");
+    comment
+}
+
+/// Writes `doc` as a doc comment on a line of its own to `code`, which ends
+/// with a complete line, for the definition that follows. Unless it starts
+/// `code`, it is separated from the previous definition by a blank line, as
+/// OCaml would attach it to both otherwise.
+fn print_doc_comment(code: &mut String, doc: &str) {
+    let doc = doc_comment(doc, "");
+    if doc.is_empty() {
+        return;
+    }
+    if !code.is_empty() {
+        writeln!(code).unwrap();
+    }
+    writeln!(code, "{}", doc).unwrap();
+}
+
 fn escape_keyword(name: String) -> String {
     if OCAML_KEYWORDS_SET.contains(name.as_str()) {
         format!("{}_", name)
@@ -104,6 +438,109 @@ fn pascal_to_snake(name: &str) -> String {
     result
 }
 
+/// Returns the path of the capnp-ocaml module generated for `node`, relative
+/// to `Reader` or `Builder` (e.g. `Expr.BinExpr`).
+fn module_path(node: schema_capnp::node::Reader) -> Result<String> {
+    Ok(display_path(node)?
+        .split('.')
+        .map(capitalize)
+        .collect::<Result<Vec<_>>>()?
+        .join("."))
+}
+
+/// Returns the name of `node` as written in the schema, qualified by the names
+/// of its enclosing nodes but not by its file (e.g. `Expr.funCall`).
+fn display_path(node: schema_capnp::node::Reader<'_>) -> Result<&str> {
+    let display_name = node.get_display_name()?.to_str()?;
+    let (_, path) = display_name.split_once(':').unwrap_or((display_name, ""));
+    Ok(path)
+}
+
+/// Returns the name of the capnp-ocaml module generated for schema file
+/// `filename` (e.g. `Example` for `schemas/example.capnp`).
+fn module_name(filename: &str) -> Result<String> {
+    capitalize(&file_stem(filename))
+}
+
+/// Returns the file name of `filename` without directory and extension.
+fn file_stem(filename: &str) -> String {
+    std::path::Path::new(filename)
+        .file_stem()
+        .map_or(filename.into(), |stem| stem.to_string_lossy())
+        .into_owned()
+}
+
+/// Returns the path of the capnp-ocaml builder module generated for `node`.
+/// Builders of nodes imported from another file are reached through the
+/// instance of that file's capnp-ocaml module declared at the top of the
+/// output (e.g. `Other.Builder.Foo`).
+fn builder_module_path(
+    node_name_map: &HashMap<u64, String>,
+    node: schema_capnp::node::Reader,
+) -> Result<String> {
+    api_module_path(node_name_map, node, "Builder", "B")
+}
+
+/// Returns the path of the capnp-ocaml reader module generated for `node`,
+/// like `builder_module_path`.
+fn reader_module_path(
+    node_name_map: &HashMap<u64, String>,
+    node: schema_capnp::node::Reader,
+) -> Result<String> {
+    api_module_path(node_name_map, node, "Reader", "R")
+}
+
+fn api_module_path(
+    node_name_map: &HashMap<u64, String>,
+    node: schema_capnp::node::Reader,
+    api: &str,
+    local_api: &str,
+) -> Result<String> {
+    if get_name(node_name_map, node.get_id())?.contains('.') {
+        let display_name = node.get_display_name()?.to_str()?;
+        let (filename, _) = display_name.split_once(':').unwrap_or((display_name, ""));
+        Ok(format!(
+            "{}.{}.{}",
+            module_name(filename)?,
+            api,
+            module_path(node)?
+        ))
+    } else {
+        Ok(format!("{}.{}", local_api, module_path(node)?))
+    }
+}
+
+/// Returns the name of node `id`, as entered by `enter_nested_nodes`,
+/// `enter_groups` or `enter_imports`.
+fn get_name(node_name_map: &HashMap<u64, String>, id: u64) -> Result<&str> {
+    node_name_map.get(&id).map(String::as_str).ok_or_else(|| {
+        Error::new(format!(
+            "refers to node @0x{:x}, which is skipped, or declared neither in this file nor in a file it imports",
+            id
+        ))
+    })
+}
+
+/// Returns the OCaml type name of node `id`. Nodes imported from another file
+/// are qualified by that file's decoder module (e.g. `Other_decoder.foo`).
+fn type_name(node_name_map: &HashMap<u64, String>, id: u64) -> Result<String> {
+    let name = get_name(node_name_map, id)?;
+    Ok(match name.rsplit_once('.') {
+        Some((module, name)) => format!("{}.{}", module, escape_keyword(name.to_string())),
+        None => escape_keyword(name.to_string()),
+    })
+}
+
+/// Returns the name of the decoder or encoder (depending on `prefix`) of node
+/// `id`, qualified like its type name.
+fn function_name(node_name_map: &HashMap<u64, String>, prefix: &str, id: u64) -> Result<String> {
+    let name = get_name(node_name_map, id)?;
+    Ok(match name.rsplit_once('.') {
+        Some((module, name)) => format!("{}.{}_{}", module, prefix, name),
+        None => format!("{}_{}", prefix, name),
+    })
+}
+
 struct ParamEnv<'a> {
     scope_id: u64,
     parameters: Vec<String>,
@@ -111,89 +548,369 @@ struct ParamEnv<'a> {
 }
 
 impl<'a> ParamEnv<'a> {
-    fn get(&self, scope_id: u64) -> &Vec<String> {
+    fn get(&self, scope_id: u64) -> Option<&Vec<String>> {
         if self.scope_id == scope_id {
-            &self.parameters
+            Some(&self.parameters)
         } else {
-            self.parent_env.unwrap().get(scope_id)
+            self.parent_env?.get(scope_id)
+        }
+    }
+
+    /// Returns the type parameters of the types defined in this environment:
+    /// those of the enclosing scopes, outermost first, followed by its own.
+    fn type_parameters(&self) -> Vec<&str> {
+        let mut parameters = self
+            .parent_env
+            .map_or_else(Vec::new, ParamEnv::type_parameters);
+        parameters.extend(self.parameters.iter().map(String::as_str));
+        parameters
+    }
+}
+
+/// Returns the environment of the generic parameters of `node`, which is
+/// nested in `parent_env`. The types nested in a generic struct or interface
+/// take its type parameters as well as their own.
+fn node_param_env<'a>(
+    node: schema_capnp::node::Reader,
+    parent_env: Option<&'a ParamEnv<'a>>,
+) -> Result<ParamEnv<'a>> {
+    let mut param_env = ParamEnv {
+        scope_id: node.get_id(),
+        parameters: Vec::new(),
+        parent_env,
+    };
+    if node.has_parameters() {
+        for param in node.get_parameters()?.iter() {
+            let name = param.get_name()?.to_str()?;
+            let snake_name = pascal_to_snake(name);
+            if param_env.type_parameters().contains(&snake_name.as_str()) {
+                return Err(Error::new(format!(
+                    "generic parameter {} clashes with a parameter of an enclosing scope",
+                    name
+                )));
+            }
+            param_env.parameters.push(snake_name);
         }
     }
+    Ok(param_env)
+}
+
+/// Returns the name of generic parameter `index` of scope `scope_id`, which
+/// must be in scope in `param_env`.
+fn parameter_name<'a>(
+    param_env: Option<&'a ParamEnv<'_>>,
+    scope_id: u64,
+    index: u16,
+) -> Result<&'a str> {
+    param_env
+        .and_then(|param_env| param_env.get(scope_id))
+        .and_then(|parameters| parameters.get(index as usize))
+        .map(String::as_str)
+        .ok_or_else(|| {
+            Error::new(format!(
+                "refers to parameter {} of @0x{:x}, which is not in scope",
+                index, scope_id
+            ))
+        })
 }
 
+/// Writes the function that decodes the elements of a list of type `type_`
+/// to `decoder`.
 fn print_type_decoder(
     decoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
     node_name_map: &HashMap<u64, String>,
     param_env: Option<&ParamEnv<'_>>,
     type_: schema_capnp::type_::Reader<'_>,
-) {
-    match type_.which().unwrap() {
+) -> Result<()> {
+    if is_plain_element(options, type_)? {
+        let decoder_name = if options.result {
+            "Result.ok"
+        } else {
+            "Fun.id"
+        };
+        write!(decoder, "{}", decoder_name).unwrap();
+        return Ok(());
+    }
+    if let Some(int_repr) = IntRepr::of_type(type_)? {
+        let repr = options.int_repr(int_repr);
+        let (function, _) = int_repr
+            .decoder(repr)
+            .expect("integers that need no conversion are plain elements");
+        if !options.result {
+            write!(decoder, "{}", function).unwrap();
+        } else if int_repr.is_fallible(options, repr) {
+            write!(decoder, "(Int_repr.checked {})", function).unwrap();
+        } else {
+            write!(decoder, "(fun n -> Ok ({} n))", function).unwrap();
+        }
+        return Ok(());
+    }
+    if let Some((function, is_fallible)) = string_decoder(options, type_)? {
+        if options.result && !is_fallible {
+            write!(decoder, "(fun s -> Ok ({} s))", function).unwrap();
+        } else {
+            write!(decoder, "{}", function).unwrap();
+        }
+        return Ok(());
+    }
+    match type_.which()? {
         schema_capnp::type_::Struct(struct_) => {
-            let type_id = struct_.get_type_id();
-            let type_name = node_name_map.get(&type_id).unwrap();
-            write!(decoder, "decode_{}", type_name).unwrap();
+            let decoder_name = function_name(node_name_map, "decode", struct_.get_type_id())?;
+            let arguments = brand_arguments(node_map, struct_)?;
+            if arguments.is_empty() {
+                write!(decoder, "{}", decoder_name).unwrap();
+            } else {
+                write!(decoder, "({}", decoder_name).unwrap();
+                for argument in arguments {
+                    write!(decoder, " ").unwrap();
+                    print_argument_decoder(
+                        decoder,
+                        options,
+                        node_map,
+                        node_name_map,
+                        param_env,
+                        argument,
+                    )?;
+                }
+                write!(decoder, ")").unwrap();
+            }
         }
         schema_capnp::type_::Enum(enum_) => {
-            let type_id = enum_.get_type_id();
-            let type_name = node_name_map.get(&type_id).unwrap();
-            write!(decoder, "decode_{}", type_name).unwrap();
+            let decoder_name = function_name(node_name_map, "decode", enum_.get_type_id())?;
+            write!(decoder, "{}", decoder_name).unwrap();
+        }
+        schema_capnp::type_::List(list)
+            if !options.result
+                && options.list_repr != ListRepr::Seq
+                && is_plain_element(options, list.get_element_type()?)? =>
+        {
+            write!(decoder, "Capnp.Array.to_{}", options.list_repr.name()).unwrap();
+        }
+        schema_capnp::type_::List(list) => {
+            write!(decoder, "(fun a -> ").unwrap();
+            print_list_decoding(
+                decoder,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+                "a",
+                options.list_repr,
+            )?;
+            write!(decoder, ")").unwrap();
         }
-        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which().unwrap() {
+        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which()? {
             schema_capnp::type_::any_pointer::Parameter(parameter) => {
                 write!(
                     decoder,
                     "decode_{}",
-                    param_env.unwrap().get(parameter.get_scope_id())
-                        [parameter.get_parameter_index() as usize]
+                    parameter_name(
+                        param_env,
+                        parameter.get_scope_id(),
+                        parameter.get_parameter_index()
+                    )?
                 )
                 .unwrap();
             }
-            _ => todo!(),
+            schema_capnp::type_::any_pointer::Unconstrained(unconstrained) => {
+                let kind = PointerKind::of_unconstrained(unconstrained)?;
+                write!(decoder, "{}", kind.decoder(options)).unwrap();
+            }
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                return Err(Error::new("implicit method parameters are not supported"))
+            }
         },
-        schema_capnp::type_::Text(()) => {
-            write!(decoder, "(fun x: string -> x)").unwrap();
+        _ => unreachable!("plain list elements are handled above"),
+    }
+    Ok(())
+}
+
+/// Returns the function that converts the strings capnp-ocaml returns for
+/// `Text` or `Data` values of type `type_`, if they need converting, and
+/// whether it returns a `result` in result mode.
+fn string_decoder(
+    options: &Options,
+    type_: schema_capnp::type_::Reader,
+) -> Result<Option<(&'static str, bool)>> {
+    Ok(match type_.which()? {
+        schema_capnp::type_::Text(()) if options.validate_utf8 => {
+            Some(("validate_utf_8", options.result))
+        }
+        schema_capnp::type_::Data(()) => options
+            .data_repr
+            .decoder()
+            .map(|function| (function, false)),
+        _ => None,
+    })
+}
+
+/// Returns whether the elements of a list of type `type_` are the values
+/// themselves, which need no decoding.
+fn is_plain_element(options: &Options, type_: schema_capnp::type_::Reader) -> Result<bool> {
+    if let Some(int_repr) = IntRepr::of_type(type_)? {
+        return Ok(options.int_repr(int_repr) == int_repr);
+    }
+    if let schema_capnp::type_::Text(()) | schema_capnp::type_::Data(()) = type_.which()? {
+        return Ok(string_decoder(options, type_)?.is_none());
+    }
+    Ok(matches!(
+        type_.which()?,
+        schema_capnp::type_::Void(())
+            | schema_capnp::type_::Bool(())
+            | schema_capnp::type_::Int8(())
+            | schema_capnp::type_::Int16(())
+            | schema_capnp::type_::Int32(())
+            | schema_capnp::type_::Int64(())
+            | schema_capnp::type_::Uint8(())
+            | schema_capnp::type_::Uint16(())
+            | schema_capnp::type_::Uint32(())
+            | schema_capnp::type_::Uint64(())
+            | schema_capnp::type_::Float32(())
+            | schema_capnp::type_::Float64(())
+            | schema_capnp::type_::Text(())
+            | schema_capnp::type_::Data(())
+            | schema_capnp::type_::Interface(_)
+    ))
+}
+
+/// Writes an OCaml expression that decodes `reader`, a list of elements of
+/// type `element_type`, into a `repr`.
+#[allow(clippy::too_many_arguments)]
+fn print_list_decoding(
+    decoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    element_type: schema_capnp::type_::Reader<'_>,
+    reader: &str,
+    repr: ListRepr,
+) -> Result<()> {
+    if is_plain_element(options, element_type)? {
+        let value = match repr {
+            ListRepr::List | ListRepr::Array => {
+                format!("Capnp.Array.to_{} {}", repr.name(), reader)
+            }
+            ListRepr::Seq => seq_decoding(reader, "(Capnp.Array.get a)"),
+        };
+        if options.result && value.starts_with('(') {
+            write!(decoder, "Ok {}", value).unwrap();
+        } else if options.result {
+            write!(decoder, "Ok ({})", value).unwrap();
+        } else {
+            write!(decoder, "{}", value).unwrap();
+        }
+        return Ok(());
+    }
+    let mut element_decoder = String::new();
+    print_type_decoder(
+        &mut element_decoder,
+        options,
+        node_map,
+        node_name_map,
+        param_env,
+        element_type,
+    )?;
+    match (options.result, repr) {
+        (true, ListRepr::List) => {
+            write!(decoder, "decode_list {} {}", element_decoder, reader).unwrap()
         }
-        _ => todo!(),
+        // Decoding all elements up front is the only way to report errors.
+        (true, ListRepr::Array | ListRepr::Seq) => write!(
+            decoder,
+            "Result.map {} (decode_list {} {})",
+            repr.of_list_function(),
+            element_decoder,
+            reader
+        )
+        .unwrap(),
+        (false, ListRepr::List | ListRepr::Array) => write!(
+            decoder,
+            "Capnp.Array.map_{} {} ~f:{}",
+            repr.name(),
+            reader,
+            element_decoder
+        )
+        .unwrap(),
+        (false, ListRepr::Seq) => write!(
+            decoder,
+            "{}",
+            seq_decoding(
+                reader,
+                &format!("(fun i -> {} (Capnp.Array.get a i))", element_decoder)
+            )
+        )
+        .unwrap(),
+    }
+    Ok(())
+}
+
+/// Returns an OCaml expression for the sequence of the elements of list
+/// reader `reader`, which `element` returns by index from list reader `a`.
+fn seq_decoding(reader: &str, element: &str) -> String {
+    let seq = format!("Seq.init (Capnp.Array.length a) {}", element);
+    if reader == "a" {
+        seq
+    } else {
+        format!("(let a = {} in {})", reader, seq)
     }
 }
 
 fn print_type_decoding(
     decoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
     node_name_map: &HashMap<u64, String>,
     param_env: Option<&ParamEnv<'_>>,
     type_: schema_capnp::type_::Reader<'_>,
     reader: &str,
-) {
-    match type_.which().unwrap() {
+) -> Result<()> {
+    match type_.which()? {
         schema_capnp::type_::Void(())
         | schema_capnp::type_::Bool(())
         | schema_capnp::type_::Int8(())
         | schema_capnp::type_::Int16(())
-        | schema_capnp::type_::Int32(())
-        | schema_capnp::type_::Int64(())
         | schema_capnp::type_::Uint8(())
         | schema_capnp::type_::Uint16(())
-        | schema_capnp::type_::Uint32(())
-        | schema_capnp::type_::Uint64(())
         | schema_capnp::type_::Float32(())
         | schema_capnp::type_::Float64(())
-        | schema_capnp::type_::Text(())
-        | schema_capnp::type_::Data(()) => {
+        | schema_capnp::type_::Interface(_) => {
             write!(decoder, "{}", reader).unwrap();
         }
+        schema_capnp::type_::Text(()) | schema_capnp::type_::Data(()) => {
+            match string_decoder(options, type_)? {
+                Some((function, _)) => write!(decoder, "{} {}", function, reader).unwrap(),
+                None => write!(decoder, "{}", reader).unwrap(),
+            }
+        }
+        schema_capnp::type_::Int32(())
+        | schema_capnp::type_::Int64(())
+        | schema_capnp::type_::Uint32(())
+        | schema_capnp::type_::Uint64(()) => {
+            let int_repr = IntRepr::of_type(type_)?.expect("an integer type");
+            let repr = options.int_repr(int_repr);
+            write!(decoder, "{}", int_repr.decoding(options, repr, reader)).unwrap();
+        }
         schema_capnp::type_::List(list) => {
-            write!(decoder, "Capnp.Array.map_list {} ~f:", reader).unwrap();
-            print_type_decoder(
+            print_list_decoding(
                 decoder,
+                options,
+                node_map,
                 node_name_map,
                 param_env,
-                list.get_element_type().unwrap(),
-            );
+                list.get_element_type()?,
+                reader,
+                options.list_repr,
+            )?;
         }
         schema_capnp::type_::Enum(enum_) => {
             write!(
                 decoder,
-                "decode_{} {}",
-                node_name_map.get(&enum_.get_type_id()).unwrap(),
+                "{} {}",
+                function_name(node_name_map, "decode", enum_.get_type_id())?,
                 reader
             )
             .unwrap();
@@ -201,508 +918,3103 @@ fn print_type_decoding(
         schema_capnp::type_::Struct(struct_) => {
             write!(
                 decoder,
-                "decode_{}",
-                node_name_map.get(&struct_.get_type_id()).unwrap()
+                "{}",
+                function_name(node_name_map, "decode", struct_.get_type_id())?
             )
             .unwrap();
-            if struct_.has_brand() {
-                let brand = struct_.get_brand().unwrap();
-                for scope in brand.get_scopes().unwrap().iter() {
-                    //let scope_id = scope.get_scope_id();
-                    match scope.which().unwrap() {
-                        schema_capnp::brand::scope::Bind(bindings) => {
-                            for binding in bindings.unwrap().iter() {
-                                match binding.which().unwrap() {
-                                    schema_capnp::brand::binding::Which::Type(t) => {
-                                        write!(decoder, " ").unwrap();
-                                        print_type_decoder(
-                                            decoder,
-                                            node_name_map,
-                                            param_env,
-                                            t.unwrap(),
-                                        );
-                                    }
-                                    _ => todo!(),
-                                }
-                            }
-                        }
-                        _ => todo!(),
-                    }
-                }
+            for argument in brand_arguments(node_map, struct_)? {
+                write!(decoder, " ").unwrap();
+                print_argument_decoder(
+                    decoder,
+                    options,
+                    node_map,
+                    node_name_map,
+                    param_env,
+                    argument,
+                )?;
             }
             write!(decoder, " {}", reader).unwrap();
         }
-        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which().unwrap() {
+        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which()? {
             schema_capnp::type_::any_pointer::Parameter(parameter) => {
                 write!(
                     decoder,
-                    "decode_{} (R.of_pointer {})",
-                    param_env.unwrap().get(parameter.get_scope_id())
-                        [parameter.get_parameter_index() as usize],
+                    "decode_{} {}",
+                    parameter_name(
+                        param_env,
+                        parameter.get_scope_id(),
+                        parameter.get_parameter_index()
+                    )?,
                     reader
                 )
                 .unwrap();
             }
-            _ => todo!(),
+            schema_capnp::type_::any_pointer::Unconstrained(unconstrained) => {
+                let kind = PointerKind::of_unconstrained(unconstrained)?;
+                write!(decoder, "{}", kind.wrap(reader)).unwrap();
+            }
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                return Err(Error::new("implicit method parameters are not supported"))
+            }
         },
-        _ => todo!(),
     }
+    Ok(())
 }
 
-fn print_type<'a>(
+/// Writes a function that decodes the values of generic argument `argument`
+/// from the pointers that hold them.
+fn print_argument_decoder(
+    decoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
     node_name_map: &HashMap<u64, String>,
     param_env: Option<&ParamEnv<'_>>,
-    type_: schema_capnp::type_::Reader<'a>,
-) {
-    match type_.which().unwrap() {
-        schema_capnp::type_::Which::Void(()) => {
-            print!("unit");
+    argument: BrandArgument<'_>,
+) -> Result<()> {
+    match argument {
+        BrandArgument::Type(type_) => match type_.which()? {
+            schema_capnp::type_::Struct(_) => {
+                write!(decoder, "(fun p -> ").unwrap();
+                print_type_decoding(
+                    decoder,
+                    options,
+                    node_map,
+                    node_name_map,
+                    param_env,
+                    type_,
+                    "(R.of_pointer p)",
+                )?;
+                write!(decoder, ")").unwrap();
+            }
+            schema_capnp::type_::AnyPointer(_) => {
+                print_type_decoder(decoder, options, node_map, node_name_map, param_env, type_)?;
+            }
+            _ => {
+                return Err(Error::new(
+                    "this type is not supported as a generic argument",
+                ))
+            }
+        },
+        BrandArgument::Inherited(scope_id, index) => {
+            write!(
+                decoder,
+                "decode_{}",
+                parameter_name(param_env, scope_id, index)?
+            )
+            .unwrap();
         }
-        schema_capnp::type_::Bool(()) => {
-            print!("bool");
+        BrandArgument::Unbound => {
+            write!(decoder, "{}", PointerKind::Any.decoder(options)).unwrap();
         }
-        schema_capnp::type_::Int8(()) => {
-            print!("int");
+    }
+    Ok(())
+}
+
+/// How a list is represented in OCaml, as chosen by `$OCaml.repr` or the
+/// `--lists=` option.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum ListRepr {
+    #[default]
+    List,
+    Array,
+    /// A sequence that decodes the elements on demand.
+    Seq,
+}
+
+impl ListRepr {
+    /// Returns the representation that `$OCaml.repr` chooses for list field
+    /// `field`, or else the one chosen by the options.
+    fn of_field(options: &Options, field: schema_capnp::field::Reader) -> Result<ListRepr> {
+        match text_annotation(field.get_annotations()?, REPR_ANNOTATION_ID)? {
+            Some(name) => ListRepr::parse(name),
+            None => Ok(options.list_repr),
         }
-        schema_capnp::type_::Int16(()) => {
-            print!("int");
+    }
+
+    /// Returns the representation named `name`.
+    fn parse(name: &str) -> Result<ListRepr> {
+        [ListRepr::List, ListRepr::Array, ListRepr::Seq]
+            .into_iter()
+            .find(|repr| repr.name() == name)
+            .ok_or_else(|| {
+                Error::new(format!(
+                    "unknown list representation \"{}\"; expected \"list\", \"array\" or \"seq\"",
+                    name
+                ))
+            })
+    }
+
+    /// Returns the name of the representation in options and annotations, and
+    /// in the names of the converter helpers.
+    fn name(self) -> &'static str {
+        match self {
+            ListRepr::List => "list",
+            ListRepr::Array => "array",
+            ListRepr::Seq => "seq",
         }
-        schema_capnp::type_::Int32(()) => {
-            print!("int32");
+    }
+
+    /// Returns the OCaml standard library module for the representation.
+    fn module_name(self) -> &'static str {
+        match self {
+            ListRepr::List => "List",
+            ListRepr::Array => "Array",
+            ListRepr::Seq => "Seq",
         }
-        schema_capnp::type_::Int64(()) => {
-            print!("int64");
+    }
+
+    /// Returns the OCaml type constructor for the representation.
+    fn type_name(self) -> &'static str {
+        match self {
+            ListRepr::List => "list",
+            ListRepr::Array => "array",
+            ListRepr::Seq => "Seq.t",
         }
-        schema_capnp::type_::Uint8(()) => {
-            print!("int");
+    }
+
+    /// Returns the function converting an OCaml list to the representation.
+    fn of_list_function(self) -> &'static str {
+        match self {
+            ListRepr::List => "Fun.id",
+            ListRepr::Array => "Array.of_list",
+            ListRepr::Seq => "List.to_seq",
         }
-        schema_capnp::type_::Uint16(()) => {
-            print!("int");
+    }
+}
+
+/// How `Data` values are represented in OCaml, as chosen by the `--data=`
+/// option.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum DataRepr {
+    /// A `string`, as capnp-ocaml returns it.
+    #[default]
+    String,
+    /// `bytes`, copied from the strings capnp-ocaml returns.
+    Bytes,
+    /// The type `data` of the generated module, a private `string`.
+    Private,
+}
+
+impl DataRepr {
+    /// Returns the representation named `name`.
+    fn parse(name: &str) -> Result<DataRepr> {
+        [DataRepr::String, DataRepr::Bytes, DataRepr::Private]
+            .into_iter()
+            .find(|repr| repr.name() == name)
+            .ok_or_else(|| {
+                Error::new(format!(
+                    "unknown data representation \"{}\"; expected \"string\", \"bytes\" or \"private\"",
+                    name
+                ))
+            })
+    }
+
+    /// Returns the name of the representation in options.
+    fn name(self) -> &'static str {
+        match self {
+            DataRepr::String => "string",
+            DataRepr::Bytes => "bytes",
+            DataRepr::Private => "private",
         }
-        schema_capnp::type_::Uint32(()) => {
-            print!("Stdint.uint32");
+    }
+
+    /// Returns the OCaml type of the representation.
+    fn type_name(self) -> &'static str {
+        match self {
+            DataRepr::String => "string",
+            DataRepr::Bytes => "bytes",
+            DataRepr::Private => "data",
         }
-        schema_capnp::type_::Uint64(()) => {
-            print!("Stdint.uint64");
+    }
+
+    /// Returns the function converting the strings that capnp-ocaml returns to
+    /// the representation, if they differ. The bytes are a copy, as the string
+    /// of an unset field is its default value, which the getter shares.
+    fn decoder(self) -> Option<&'static str> {
+        match self {
+            DataRepr::Bytes => Some("Bytes.of_string"),
+            DataRepr::String | DataRepr::Private => None,
         }
-        schema_capnp::type_::Float32(()) => {
-            print!("float");
+    }
+
+    /// Returns the function converting values of the representation to the
+    /// strings that capnp-ocaml expects, if they differ. The setters copy the
+    /// string right away, so the bytes need no copy.
+    fn encoder(self) -> Option<&'static str> {
+        match self {
+            DataRepr::Bytes => Some("Bytes.unsafe_to_string"),
+            DataRepr::String | DataRepr::Private => None,
         }
-        schema_capnp::type_::Float64(()) => {
-            print!("float");
+    }
+
+    /// Returns the OCaml expression for `data` in the representation.
+    fn literal(self, data: &[u8]) -> String {
+        match self {
+            DataRepr::Bytes => format!("Bytes.of_string {}", string_literal(data)),
+            DataRepr::String | DataRepr::Private => string_literal(data),
+        }
+    }
+}
+
+/// How a 32- or 64-bit integer is represented in OCaml, as chosen by
+/// `$OCaml.repr` or the `--int32=`, `--int64=`, `--uint32=` and `--uint64=`
+/// options. The representation that capnp-ocaml uses for an integer type is
+/// the default, and the one the others are converted from and to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum IntRepr {
+    Int,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+}
+
+impl IntRepr {
+    /// Returns the representation that capnp-ocaml uses for `type_`, if it is
+    /// a 32- or 64-bit integer type.
+    fn of_type(type_: schema_capnp::type_::Reader) -> Result<Option<IntRepr>> {
+        Ok(match type_.which()? {
+            schema_capnp::type_::Int32(()) => Some(IntRepr::Int32),
+            schema_capnp::type_::Int64(()) => Some(IntRepr::Int64),
+            schema_capnp::type_::Uint32(()) => Some(IntRepr::Uint32),
+            schema_capnp::type_::Uint64(()) => Some(IntRepr::Uint64),
+            _ => None,
+        })
+    }
+
+    /// Returns the name of the representation in options and annotations.
+    fn name(self) -> &'static str {
+        match self {
+            IntRepr::Int => "int",
+            IntRepr::Int32 => "int32",
+            IntRepr::Int64 => "int64",
+            IntRepr::Uint32 => "uint32",
+            IntRepr::Uint64 => "uint64",
+        }
+    }
+
+    /// Returns the OCaml type of the representation.
+    fn type_name(self) -> &'static str {
+        match self {
+            IntRepr::Int => "int",
+            IntRepr::Int32 => "int32",
+            IntRepr::Int64 => "int64",
+            IntRepr::Uint32 => "Stdint.uint32",
+            IntRepr::Uint64 => "Stdint.uint64",
+        }
+    }
+
+    /// Returns the representations of the integer type that capnp-ocaml
+    /// represents as `self`, starting with `self`.
+    fn alternatives(self) -> &'static [IntRepr] {
+        match self {
+            IntRepr::Int32 => &[IntRepr::Int32, IntRepr::Int],
+            IntRepr::Int64 => &[IntRepr::Int64, IntRepr::Int],
+            IntRepr::Uint32 => &[IntRepr::Uint32, IntRepr::Int, IntRepr::Int64],
+            IntRepr::Uint64 => &[IntRepr::Uint64, IntRepr::Int, IntRepr::Int64],
+            IntRepr::Int => &[IntRepr::Int],
+        }
+    }
+
+    /// Returns the representation named `name` of the integer type that
+    /// capnp-ocaml represents as `self`.
+    fn parse(self, name: &str) -> Result<IntRepr> {
+        let alternatives = self.alternatives();
+        if let Some(&repr) = alternatives.iter().find(|repr| repr.name() == name) {
+            return Ok(repr);
+        }
+        let names: Vec<String> = alternatives
+            .iter()
+            .map(|repr| format!("\"{}\"", repr.name()))
+            .collect();
+        Err(Error::new(format!(
+            "unknown representation \"{}\" of {} integers; expected {} or {}",
+            name,
+            self.name(),
+            names[..names.len() - 1].join(", "),
+            names[names.len() - 1]
+        )))
+    }
+
+    /// Returns the function converting integers from `self` to `repr`, if they
+    /// differ, and whether it raises `Invalid_argument` for integers out of the
+    /// range of `repr`.
+    fn decoder(self, repr: IntRepr) -> Option<(&'static str, bool)> {
+        match (self, repr) {
+            (IntRepr::Int32, IntRepr::Int) => Some(("Int32.to_int", false)),
+            (IntRepr::Int64, IntRepr::Int) => Some(("Int_repr.int_of_int64", true)),
+            (IntRepr::Uint32, IntRepr::Int) => Some(("Stdint.Uint32.to_int", false)),
+            (IntRepr::Uint32, IntRepr::Int64) => Some(("Stdint.Uint32.to_int64", false)),
+            (IntRepr::Uint64, IntRepr::Int) => Some(("Int_repr.int_of_uint64", true)),
+            (IntRepr::Uint64, IntRepr::Int64) => Some(("Int_repr.int64_of_uint64", true)),
+            _ => None,
+        }
+    }
+
+    /// Returns the function converting integers from `repr` to `self`, if they
+    /// differ. It raises `Invalid_argument` for integers out of the range of
+    /// `self`.
+    fn encoder(self, repr: IntRepr) -> Option<&'static str> {
+        match (self, repr) {
+            (IntRepr::Int32, IntRepr::Int) => Some("Int_repr.int32_of_int"),
+            (IntRepr::Int64, IntRepr::Int) => Some("Int64.of_int"),
+            (IntRepr::Uint32, IntRepr::Int) => Some("Int_repr.uint32_of_int"),
+            (IntRepr::Uint32, IntRepr::Int64) => Some("Int_repr.uint32_of_int64"),
+            (IntRepr::Uint64, IntRepr::Int) => Some("Int_repr.uint64_of_int"),
+            (IntRepr::Uint64, IntRepr::Int64) => Some("Int_repr.uint64_of_int64"),
+            _ => None,
+        }
+    }
+
+    /// Returns whether decoding an integer that capnp-ocaml represents as
+    /// `self` into `repr` can fail, and therefore yields a `result` in result
+    /// mode.
+    fn is_fallible(self, options: &Options, repr: IntRepr) -> bool {
+        options.result && matches!(self.decoder(repr), Some((_, true)))
+    }
+
+    /// Returns an OCaml expression that converts `value`, represented as
+    /// `self`, to `repr`, as the decoders return it if the conversion can fail.
+    fn decoding(self, options: &Options, repr: IntRepr, value: &str) -> String {
+        match self.decoder(repr) {
+            None => value.to_string(),
+            Some((function, true)) if options.result => {
+                format!("Int_repr.checked {} {}", function, value)
+            }
+            Some((function, _)) => format!("{} {}", function, value),
+        }
+    }
+
+    /// Returns an OCaml expression that converts `value`, represented as
+    /// `repr`, to `self`.
+    fn encoding(self, repr: IntRepr, value: &str) -> String {
+        match self.encoder(repr) {
+            None => value.to_string(),
+            Some(function) => format!("({} {})", function, value),
+        }
+    }
+
+    /// Returns the OCaml literal of integer `value` represented as `self`.
+    fn literal(self, value: i128) -> Result<String> {
+        let (min, max) = match self {
+            // OCaml's native integers have 63 bits on 64-bit targets.
+            IntRepr::Int => (-(1 << 62), (1 << 62) - 1),
+            IntRepr::Int32 => (i32::MIN.into(), i32::MAX.into()),
+            IntRepr::Int64 => (i64::MIN.into(), i64::MAX.into()),
+            IntRepr::Uint32 => (0, u32::MAX.into()),
+            IntRepr::Uint64 => (0, u64::MAX.into()),
+        };
+        if value < min || value > max {
+            return Err(Error::new(format!(
+                "{} is out of the range of {}",
+                value,
+                self.type_name()
+            )));
+        }
+        Ok(match self {
+            IntRepr::Int => value.to_string(),
+            IntRepr::Int32 => format!("{}l", value),
+            IntRepr::Int64 => format!("{}L", value),
+            IntRepr::Uint32 => format!("Stdint.Uint32.of_string \"{}\"", value),
+            IntRepr::Uint64 => format!("Stdint.Uint64.of_string \"{}\"", value),
+        })
+    }
+}
+
+/// How a field is represented in OCaml, as chosen by `$OCaml.repr` and, for
+/// integers, the command line options.
+#[derive(Clone, Copy)]
+enum FieldRepr {
+    List(ListRepr),
+    Int(IntRepr),
+    Other,
+}
+
+impl FieldRepr {
+    /// Returns the representation of `field`.
+    fn of_field(options: &Options, field: schema_capnp::field::Reader) -> Result<FieldRepr> {
+        if let schema_capnp::field::Slot(slot) = field.which()? {
+            let type_ = slot.get_type()?;
+            if let Some(int_repr) = IntRepr::of_type(type_)? {
+                return match text_annotation(field.get_annotations()?, REPR_ANNOTATION_ID)? {
+                    Some(name) => Ok(FieldRepr::Int(int_repr.parse(name)?)),
+                    None => Ok(FieldRepr::Int(options.int_repr(int_repr))),
+                };
+            }
+            if let schema_capnp::type_::List(_) = type_.which()? {
+                return Ok(FieldRepr::List(ListRepr::of_field(options, field)?));
+            }
+        }
+        if text_annotation(field.get_annotations()?, REPR_ANNOTATION_ID)?.is_some() {
+            return Err(Error::new(
+                "only list fields and 32- and 64-bit integer fields can have a representation",
+            ));
+        }
+        Ok(FieldRepr::Other)
+    }
+
+    /// Returns whether decoding a field of type `type_` represented as `self`
+    /// can fail, and therefore yields a `result` in result mode.
+    fn is_fallible(self, options: &Options, type_: schema_capnp::type_::Reader) -> Result<bool> {
+        match (IntRepr::of_type(type_)?, self) {
+            (Some(int_repr), FieldRepr::Int(repr)) => Ok(int_repr.is_fallible(options, repr)),
+            _ => is_fallible(options, type_),
+        }
+    }
+
+    /// Returns the representation of the integers that capnp-ocaml represents
+    /// as `int_repr` in a field represented as `self`.
+    fn int(self, options: &Options, int_repr: IntRepr) -> IntRepr {
+        match self {
+            FieldRepr::Int(repr) => repr,
+            FieldRepr::List(_) | FieldRepr::Other => options.int_repr(int_repr),
+        }
+    }
+
+    /// Returns the representation of the outermost list of a list field
+    /// represented as `self`.
+    fn list(self, options: &Options) -> ListRepr {
+        match self {
+            FieldRepr::List(repr) => repr,
+            FieldRepr::Int(_) | FieldRepr::Other => options.list_repr,
+        }
+    }
+}
+
+/// Prints the type of a field of type `type_` represented as `repr`.
+fn print_field_type(
+    types: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader<'_>,
+    repr: FieldRepr,
+) -> Result<()> {
+    match (type_.which()?, repr) {
+        (schema_capnp::type_::List(list), FieldRepr::List(repr)) => {
+            print_type(
+                types,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+            )?;
+            write!(types, " {}", repr.type_name()).unwrap();
+        }
+        (_, FieldRepr::Int(repr)) => write!(types, "{}", repr.type_name()).unwrap(),
+        _ => print_type(types, options, node_map, node_name_map, param_env, type_)?,
+    }
+    Ok(())
+}
+
+/// Like `print_type_decoding`, for a field represented as `repr`.
+#[allow(clippy::too_many_arguments)]
+fn print_field_type_decoding(
+    decoder: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader<'_>,
+    reader: &str,
+    repr: FieldRepr,
+) -> Result<()> {
+    match type_.which()? {
+        schema_capnp::type_::List(list) => {
+            print_list_decoding(
+                decoder,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+                reader,
+                repr.list(options),
+            )?;
+        }
+        _ => match (IntRepr::of_type(type_)?, repr) {
+            (Some(int_repr), FieldRepr::Int(repr)) => {
+                write!(decoder, "{}", int_repr.decoding(options, repr, reader)).unwrap();
+            }
+            _ => print_type_decoding(
+                decoder,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                type_,
+                reader,
+            )?,
+        },
+    }
+    Ok(())
+}
+
+fn print_type<'a>(
+    types: &mut String,
+    options: &Options,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    type_: schema_capnp::type_::Reader<'a>,
+) -> Result<()> {
+    match type_.which()? {
+        schema_capnp::type_::Which::Void(()) => {
+            write!(types, "unit").unwrap();
+        }
+        schema_capnp::type_::Bool(()) => {
+            write!(types, "bool").unwrap();
+        }
+        schema_capnp::type_::Int8(()) => {
+            write!(types, "int").unwrap();
+        }
+        schema_capnp::type_::Int16(()) => {
+            write!(types, "int").unwrap();
+        }
+        schema_capnp::type_::Int32(()) => {
+            write!(types, "{}", options.int_repr(IntRepr::Int32).type_name()).unwrap();
+        }
+        schema_capnp::type_::Int64(()) => {
+            write!(types, "{}", options.int_repr(IntRepr::Int64).type_name()).unwrap();
+        }
+        schema_capnp::type_::Uint8(()) => {
+            write!(types, "int").unwrap();
+        }
+        schema_capnp::type_::Uint16(()) => {
+            write!(types, "int").unwrap();
+        }
+        schema_capnp::type_::Uint32(()) => {
+            write!(types, "{}", options.int_repr(IntRepr::Uint32).type_name()).unwrap();
+        }
+        schema_capnp::type_::Uint64(()) => {
+            write!(types, "{}", options.int_repr(IntRepr::Uint64).type_name()).unwrap();
+        }
+        schema_capnp::type_::Float32(()) => {
+            write!(types, "float").unwrap();
+        }
+        schema_capnp::type_::Float64(()) => {
+            write!(types, "float").unwrap();
+        }
+        schema_capnp::type_::Text(()) => {
+            write!(types, "string").unwrap();
+        }
+        schema_capnp::type_::Data(()) => {
+            write!(types, "{}", options.data_repr.type_name()).unwrap();
+        }
+        schema_capnp::type_::List(list) => {
+            print_type(
+                types,
+                options,
+                node_map,
+                node_name_map,
+                param_env,
+                list.get_element_type()?,
+            )?;
+            write!(types, " {}", options.list_repr.type_name()).unwrap();
+        }
+        schema_capnp::type_::Enum(enum_) => {
+            write!(types, "{}", type_name(node_name_map, enum_.get_type_id())?).unwrap();
+        }
+        schema_capnp::type_::Struct(struct_) => {
+            let arguments = brand_arguments(node_map, struct_)?;
+            if arguments.len() > 1 {
+                write!(types, "(").unwrap();
+            }
+            for (index, argument) in arguments.iter().enumerate() {
+                if index > 0 {
+                    write!(types, ", ").unwrap();
+                }
+                match *argument {
+                    BrandArgument::Type(type_) => {
+                        print_type(types, options, node_map, node_name_map, param_env, type_)?
+                    }
+                    BrandArgument::Inherited(scope_id, index) => {
+                        write!(types, "'{}", parameter_name(param_env, scope_id, index)?).unwrap()
+                    }
+                    BrandArgument::Unbound => write!(types, "any_pointer").unwrap(),
+                }
+            }
+            match arguments.len() {
+                0 => {}
+                1 => write!(types, " ").unwrap(),
+                _ => write!(types, ") ").unwrap(),
+            }
+            write!(
+                types,
+                "{}",
+                type_name(node_name_map, struct_.get_type_id())?
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::Interface(interface) => {
+            write!(
+                types,
+                "{} option",
+                type_name(node_name_map, interface.get_type_id())?
+            )
+            .unwrap();
+        }
+        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which()? {
+            schema_capnp::type_::any_pointer::Parameter(parameter) => {
+                write!(
+                    types,
+                    "'{}",
+                    parameter_name(
+                        param_env,
+                        parameter.get_scope_id(),
+                        parameter.get_parameter_index()
+                    )?
+                )
+                .unwrap();
+            }
+            schema_capnp::type_::any_pointer::Unconstrained(unconstrained) => {
+                let kind = PointerKind::of_unconstrained(unconstrained)?;
+                write!(types, "{}", kind.type_name()).unwrap();
+            }
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                return Err(Error::new("implicit method parameters are not supported"))
+            }
+        },
+    }
+    Ok(())
+}
+
+fn enter_nested_nodes(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &mut HashMap<u64, String>,
+    qualifier: &str,
+    nested_nodes: capnp::struct_list::Reader<schema_capnp::node::nested_node::Owned>,
+) -> Result<()> {
+    for nested_node in nested_nodes.iter() {
+        let nested_id = nested_node.get_id();
+        let node = get_node(node_map, nested_id)?;
+        let annotations = node.get_annotations()?;
+        if has_annotation(annotations, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        let nested_qualifier =
+            match name_annotation(annotations, false).map_err(|error| error.in_node(node))? {
+                Some(name) => name.to_string(),
+                None => {
+                    let nested_name = pascal_to_snake(nested_node.get_name()?.to_str()?);
+                    if qualifier.is_empty() {
+                        nested_name
+                    } else {
+                        format!("{}_{}", qualifier, nested_name)
+                    }
+                }
+            };
+        node_name_map.insert(nested_id, nested_qualifier.clone());
+        enter_groups(node_map, node_name_map, &nested_qualifier, node)?;
+        enter_nested_nodes(
+            node_map,
+            node_name_map,
+            &nested_qualifier,
+            node.get_nested_nodes()?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Names the nodes declared by the files imported by `requested_file` after
+/// the decoder modules generated for those files. Returns the names of the
+/// capnp-ocaml modules of the imported files that declare structs, which
+/// encoders need to initialize their pointers.
+fn enter_imports(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &mut HashMap<u64, String>,
+    requested_file: requested_file::Reader,
+) -> Result<Vec<String>> {
+    let mut modules = Vec::new();
+    for import in requested_file.get_imports()?.iter() {
+        let Some(file_node) = node_map.get(&import.get_id()) else {
+            continue;
+        };
+        let filename = import.get_name()?.to_str()?;
+        let mut import_name_map = HashMap::new();
+        enter_nested_nodes(
+            node_map,
+            &mut import_name_map,
+            "",
+            file_node.get_nested_nodes()?,
+        )?;
+        let decoder_module = format!("{}_decoder", module_name(filename)?);
+        let mut declares_structs = false;
+        for (id, name) in import_name_map {
+            if let schema_capnp::node::Struct(_) = get_node(node_map, id)?.which()? {
+                declares_structs = true;
+            }
+            node_name_map.insert(id, format!("{}.{}", decoder_module, name));
+        }
+        if declares_structs {
+            modules.push(module_name(filename)?);
+        }
+    }
+    Ok(modules)
+}
+
+/// Names the groups of struct (or group) `node` after the field that holds
+/// them, or its `$OCaml.name`, qualified by `qualifier`.
+fn enter_groups(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &mut HashMap<u64, String>,
+    qualifier: &str,
+    node: schema_capnp::node::Reader,
+) -> Result<()> {
+    let schema_capnp::node::Struct(struct_node) = node.which()? else {
+        return Ok(());
+    };
+    for field in struct_node.get_fields()?.iter() {
+        if let schema_capnp::field::Group(group) = field.which()? {
+            let group_id = group.get_type_id();
+            let field_name = field.get_name()?.to_str()?;
+            // The `$OCaml.name` of a union member is its constructor.
+            let name = if field.get_discriminant_value() == NO_DISCRIMINANT {
+                group_name_annotation(node_map, field, group, false)
+                    .map_err(|error| error.in_member(field_name).in_node(node))?
+            } else {
+                None
+            };
+            let group_name = match name {
+                Some(name) => name.to_string(),
+                None => pascal_to_snake(field_name),
+            };
+            let group_qualifier = format!("{}_{}", qualifier, group_name);
+            node_name_map.insert(group_id, group_qualifier.clone());
+            let group_node = get_node(node_map, group_id)?;
+            enter_groups(node_map, node_name_map, &group_qualifier, group_node)?;
+        }
+    }
+    Ok(())
+}
+
+/// The definitions and signatures generated so far. `types`, `decoder`,
+/// `encoder` and `converters` hold the definitions of the current node, which
+/// then move to `definitions`. The implementation lists all types, then all
+/// decoders, then all encoders, in recursive groups; the interface lists the
+/// same types followed by the signatures.
+struct Output<'a> {
+    options: Options,
+    /// The source info of each node, which holds its doc comments.
+    docs: &'a HashMap<u64, schema_capnp::node::source_info::Reader<'a>>,
+    types: String,
+    decoder: String,
+    encoder: String,
+    interface: String,
+    defaults: String,
+    constants: String,
+    /// The functions that the derivers expect for capability types, which go
+    /// between the capability types and the other types in the
+    /// implementation.
+    deriving_helpers: String,
+    /// The converters to and from the `--json` and `--sexp` formats.
+    converters: String,
+    /// The functions that decode and encode the values of untyped pointers,
+    /// which go before the encoders in the implementation.
+    pointer_helpers: String,
+    /// The doc comment of the first type of the current node, which goes
+    /// before the `type` or `and` keyword that starts it in its group.
+    doc: String,
+    definitions: Vec<Definition>,
+}
+
+/// The types, decoders, encoders and converters generated for a struct, group
+/// or enum, without the keywords that start them.
+struct Definition {
+    node_id: u64,
+    /// The structs, groups and enums whose definitions this one refers to.
+    dependencies: Vec<u64>,
+    /// Whether there are two types, a record and its `<name>_union` type, whose
+    /// decoders and encoders call each other.
+    has_union_type: bool,
+    doc: String,
+    types: String,
+    decoder: String,
+    encoder: String,
+    converters: String,
+}
+
+/// The code generation options, given as command line arguments and by the
+/// `$OCaml.options` annotation of the schema file.
+#[derive(Clone, Default)]
+struct Options {
+    /// Whether decoders return a `result` instead of raising an exception
+    /// (`--result`).
+    result: bool,
+    /// Whether enums and unions get an extra `Unknown of int` constructor for
+    /// enumerants and discriminants that are not in the schema (`--unknown`).
+    unknown: bool,
+    /// Whether pointer fields outside unions that have no explicit default
+    /// become `option`s, `None` standing for a pointer that is not set
+    /// (`--optional-pointers`).
+    optional_pointers: bool,
+    /// The derivers listed in the `[@@deriving ...]` attribute added to every
+    /// generated type (`--deriving=show,eq,ord`).
+    deriving: Vec<String>,
+    /// The formats that converters are generated for (`--json`, `--sexp`).
+    formats: Vec<Format>,
+    /// The representations of 32- and 64-bit integers, by the representation
+    /// capnp-ocaml uses for them, that differ from it (`--int32=int`,
+    /// `--int64=int`, `--uint32=int`, `--uint64=int64`).
+    int_reprs: HashMap<IntRepr, IntRepr>,
+    /// The representation of lists, outside fields with their own
+    /// (`--lists=array`, `--lists=seq`).
+    list_repr: ListRepr,
+    /// The representation of `Data` values (`--data=bytes`, `--data=private`).
+    data_repr: DataRepr,
+    /// Whether decoders check that `Text` values are valid UTF-8
+    /// (`--validate-utf8`).
+    validate_utf8: bool,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Options> {
+        let mut options = Options::default();
+        for arg in args {
+            options.set(&arg)?;
+        }
+        Ok(options)
+    }
+
+    /// Sets the option given as command line argument `arg`, overriding its
+    /// previous value.
+    fn set(&mut self, arg: &str) -> Result<()> {
+        if let Some(derivers) = arg.strip_prefix("--deriving=") {
+            self.deriving = derivers.split(',').map(|d| d.trim().to_string()).collect();
+            if self.deriving.iter().any(String::is_empty) {
+                return Err(Error::new(format!("invalid list of derivers in {}", arg)));
+            }
+            return Ok(());
+        }
+        if let Some(name) = arg.strip_prefix("--lists=") {
+            self.list_repr = ListRepr::parse(name)?;
+            return Ok(());
+        }
+        if let Some(name) = arg.strip_prefix("--data=") {
+            self.data_repr = DataRepr::parse(name)?;
+            return Ok(());
+        }
+        if let Some((option, name)) = arg.split_once('=') {
+            let int_repr = [
+                IntRepr::Int32,
+                IntRepr::Int64,
+                IntRepr::Uint32,
+                IntRepr::Uint64,
+            ]
+            .into_iter()
+            .find(|int_repr| option.strip_prefix("--") == Some(int_repr.name()));
+            if let Some(int_repr) = int_repr {
+                self.int_reprs.insert(int_repr, int_repr.parse(name)?);
+                return Ok(());
+            }
+        }
+        match arg {
+            "--result" => self.result = true,
+            "--unknown" => self.unknown = true,
+            "--optional-pointers" => self.optional_pointers = true,
+            "--validate-utf8" => self.validate_utf8 = true,
+            "--json" if !self.formats.contains(&Format::Json) => self.formats.push(Format::Json),
+            "--sexp" if !self.formats.contains(&Format::Sexp) => self.formats.push(Format::Sexp),
+            "--json" | "--sexp" => {}
+            _ => return Err(Error::new(format!("unknown option {}", arg))),
+        }
+        Ok(())
+    }
+
+    /// Returns the representation of the integers that capnp-ocaml represents
+    /// as `int_repr`, outside fields with their own.
+    fn int_repr(&self, int_repr: IntRepr) -> IntRepr {
+        self.int_reprs.get(&int_repr).copied().unwrap_or(int_repr)
+    }
+
+    /// Returns whether `--deriving` lists `deriver`, possibly with options.
+    fn derives(&self, deriver: &str) -> bool {
+        self.deriving
+            .iter()
+            .any(|item| item.split_whitespace().next() == Some(deriver))
+    }
+}
+
+/// Returns decoded value `value` as the decoders return it.
+fn ok(options: &Options, value: &str) -> String {
+    if options.result {
+        format!("Ok {}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns the decoded value for an enumerant or discriminant `n` that is not
+/// in the schema, as the decoders return it.
+fn unknown_value(options: &Options) -> &'static str {
+    if options.result {
+        "Ok (Unknown n)"
+    } else {
+        "Unknown n"
+    }
+}
+
+/// Returns the type of the decoders' results for values of OCaml type `type_`.
+fn decoded_type(options: &Options, type_: &str) -> String {
+    if options.result {
+        format!("({}, decode_error) result", type_)
+    } else {
+        type_.to_string()
+    }
+}
+
+/// Returns whether decoding a value of type `type_` can fail, and therefore
+/// yields a `result` in result mode.
+fn is_fallible(options: &Options, type_: schema_capnp::type_::Reader) -> Result<bool> {
+    if !options.result {
+        return Ok(false);
+    }
+    if let schema_capnp::type_::AnyPointer(any_pointer) = type_.which()? {
+        return Ok(PointerKind::of_any_pointer(any_pointer)?.is_none());
+    }
+    if let Some(int_repr) = IntRepr::of_type(type_)? {
+        return Ok(int_repr.is_fallible(options, options.int_repr(int_repr)));
+    }
+    if let schema_capnp::type_::Text(()) | schema_capnp::type_::Data(()) = type_.which()? {
+        return Ok(matches!(string_decoder(options, type_)?, Some((_, true))));
+    }
+    Ok(!matches!(
+        type_.which()?,
+        schema_capnp::type_::Void(())
+            | schema_capnp::type_::Bool(())
+            | schema_capnp::type_::Int8(())
+            | schema_capnp::type_::Int16(())
+            | schema_capnp::type_::Int32(())
+            | schema_capnp::type_::Int64(())
+            | schema_capnp::type_::Uint8(())
+            | schema_capnp::type_::Uint16(())
+            | schema_capnp::type_::Uint32(())
+            | schema_capnp::type_::Uint64(())
+            | schema_capnp::type_::Float32(())
+            | schema_capnp::type_::Float64(())
+            | schema_capnp::type_::Text(())
+            | schema_capnp::type_::Data(())
+            | schema_capnp::type_::Interface(_)
+    ))
+}
+
+/// Returns whether record field `slot` is represented as an `option`. A
+/// pointer field with an explicit default is not, since a pointer that is not
+/// set stands for that default.
+fn is_optional(options: &Options, slot: schema_capnp::field::slot::Reader) -> Result<bool> {
+    if !options.optional_pointers || slot.get_had_explicit_default() {
+        return Ok(false);
+    }
+    Ok(matches!(
+        slot.get_type()?.which()?,
+        schema_capnp::type_::Text(())
+            | schema_capnp::type_::Data(())
+            | schema_capnp::type_::List(_)
+            | schema_capnp::type_::Struct(_)
+            | schema_capnp::type_::AnyPointer(_)
+    ))
+}
+
+/// Returns `name` with its first letter in upper case.
+fn capitalize(name: &str) -> Result<String> {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return Err(Error::new("cannot capitalize an empty name"));
+    };
+    Ok(first.to_uppercase().collect::<String>() + chars.as_str())
+}
+
+/// Returns the type arguments to apply a type defined in `param_env` to
+/// (e.g. `'t ` or `('k, 'v) `).
+fn type_args(param_env: &ParamEnv<'_>) -> String {
+    let parameters = param_env.type_parameters();
+    match parameters.len() {
+        0 => String::new(),
+        1 => format!("'{} ", parameters[0]),
+        _ => format!(
+            "({}) ",
+            parameters
+                .iter()
+                .map(|name| format!("'{}", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Returns the decoder (or encoder, depending on `prefix`) arguments to pass
+/// to a decoder defined in `param_env` (e.g. ` decode_t`).
+fn fun_args(param_env: &ParamEnv<'_>, prefix: &str) -> String {
+    param_env
+        .type_parameters()
+        .iter()
+        .map(|name| format!(" {}_{}", prefix, name))
+        .collect()
+}
+
+/// Returns the types of the decoder arguments that the decoders defined in
+/// `param_env` take (e.g. `(R.pointer_t -> 't) -> `). Like the encoder
+/// arguments, they take the pointers that hold the values of the parameters.
+fn decoder_param_types(options: &Options, param_env: &ParamEnv<'_>) -> String {
+    param_env
+        .type_parameters()
+        .iter()
+        .map(|param| {
+            format!(
+                "(R.pointer_t -> {}) -> ",
+                decoded_type(options, &format!("'{}", param))
+            )
+        })
+        .collect()
+}
+
+/// Ends the current definition in the type chain with the `--deriving`
+/// attribute, if any. ppx_deriving needs the attribute on every type of a
+/// recursive definition.
+fn print_deriving_attribute(out: &mut Output) {
+    if !out.options.deriving.is_empty() {
+        write!(
+            out.types,
+            "\n[@@deriving {}]",
+            out.options.deriving.join(", ")
+        )
+        .unwrap();
+    }
+}
+
+/// Starts the next type of the current node and its decoder and encoder,
+/// with doc comment `doc` on the type.
+fn print_definition_separators(out: &mut Output, doc: &str) {
+    if out.types.is_empty() {
+        out.doc = doc.to_string();
+    } else {
+        print_deriving_attribute(out);
+        writeln!(out.types).unwrap();
+        print_doc_comment(&mut out.types, doc);
+        write!(out.types, "and ").unwrap();
+        write!(out.decoder, "\nand ").unwrap();
+        write!(out.encoder, "\nand ").unwrap();
+    }
+}
+
+/// Moves the definitions of node `node_id`, which refer to those of
+/// `dependencies`, from `out` to `out.definitions`.
+fn end_definition(out: &mut Output, node_id: u64, dependencies: Vec<u64>, has_union_type: bool) {
+    let definition = Definition {
+        node_id,
+        dependencies,
+        has_union_type,
+        doc: std::mem::take(&mut out.doc),
+        types: std::mem::take(&mut out.types),
+        decoder: std::mem::take(&mut out.decoder),
+        encoder: std::mem::take(&mut out.encoder),
+        converters: std::mem::take(&mut out.converters),
+    };
+    out.definitions.push(definition);
+}
+
+/// Starts the definitions of type `name` and of its decoder and encoder, which
+/// read from `reader_path` and write to `builder_path`, and adds the
+/// signatures of the decoder and encoder to the interface. Doc comment `doc`
+/// goes on the type and on the signature of the decoder.
+fn print_definition_header(
+    out: &mut Output,
+    param_env: &ParamEnv<'_>,
+    name: &str,
+    doc: &str,
+    reader_path: &str,
+    builder_path: &str,
+) {
+    print_definition_separators(out, doc);
+    print_doc_comment(&mut out.interface, doc);
+    let escaped_name = escape_keyword(name.to_string());
+    let type_args = type_args(param_env);
+    writeln!(out.types, "{}{} =", type_args, escaped_name).unwrap();
+    let result_type = decoded_type(&out.options, &format!("{}{}", type_args, escaped_name));
+    if param_env.type_parameters().is_empty() {
+        writeln!(out.decoder, "decode_{} r: {} =", name, result_type).unwrap();
+        write!(
+            out.encoder,
+            "encode_{} b (v: {}): unit =",
+            name, escaped_name
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val decode_{}: {}.t -> {}",
+            name, reader_path, result_type
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val encode_{}: {}.t -> {} -> unit",
+            name, builder_path, escaped_name
+        )
+        .unwrap();
+    } else {
+        let mut type_vars = String::new();
+        let decoder_fun_param_types = decoder_param_types(&out.options, param_env);
+        let mut encoder_fun_param_types = String::new();
+        for param in param_env.type_parameters() {
+            write!(type_vars, " '{}", param).unwrap();
+            write!(
+                encoder_fun_param_types,
+                "(B.pointer_t -> '{} -> unit) -> ",
+                param
+            )
+            .unwrap();
+        }
+        writeln!(
+            out.decoder,
+            "decode_{}:{}. {}{}.t -> {} = fun{} r ->",
+            name,
+            type_vars,
+            decoder_fun_param_types,
+            reader_path,
+            result_type,
+            fun_args(param_env, "decode")
+        )
+        .unwrap();
+        write!(
+            out.encoder,
+            "encode_{}:{}. {}{}.t -> {}{} -> unit = fun{} b v ->",
+            name,
+            type_vars,
+            encoder_fun_param_types,
+            builder_path,
+            type_args,
+            escaped_name,
+            fun_args(param_env, "encode")
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val decode_{}: {}{}.t -> {}",
+            name, decoder_fun_param_types, reader_path, result_type
+        )
+        .unwrap();
+        writeln!(
+            out.interface,
+            "val encode_{}: {}{}.t -> {}{} -> unit",
+            name, encoder_fun_param_types, builder_path, type_args, escaped_name
+        )
+        .unwrap();
+    }
+}
+
+/// Prints the type, decoder and encoder of struct or group `node_id`, preceded
+/// by those of the groups it contains. A struct that has both
+/// an anonymous union and fields outside it becomes a record whose `union`
+/// field holds a separate `<name>_union` variant type.
+fn print_struct(
+    out: &mut Output,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    node_id: u64,
+    reader_path: &str,
+    builder_path: &str,
+) -> Result<()> {
+    let node = get_node(node_map, node_id)?;
+    let struct_node = get_struct_node(node)?;
+    print_group_types(
+        out,
+        node_map,
+        node_name_map,
+        param_env,
+        struct_node,
+        reader_path,
+        builder_path,
+    )?;
+    let name = get_name(node_name_map, node_id)?;
+    let has_union = struct_node.get_discriminant_count() > 0;
+    let mut record_fields = Vec::new();
+    collect_record_fields(
+        node_map,
+        out.docs,
+        node_id,
+        reader_path,
+        builder_path,
+        "r",
+        "b",
+        &mut record_fields,
+    )?;
+    let has_non_union_fields = !record_fields.is_empty();
+    if !has_union && !has_non_union_fields {
+        return Err(Error::new("all fields of this struct are skipped"));
+    }
+    let doc = node_doc(out.docs, node_id)?;
+    print_definition_header(out, param_env, name, doc, reader_path, builder_path);
+    if has_non_union_fields {
+        print_record(
+            out,
+            node_map,
+            node_name_map,
+            param_env,
+            node_id,
+            name,
+            reader_path,
+            builder_path,
+            false,
+        )?;
+        if has_union {
+            let union_name = format!("{}_union", name);
+            print_definition_header(out, param_env, &union_name, "", reader_path, builder_path);
+        }
+    }
+    if has_union {
+        print_union(
+            out,
+            node_map,
+            node_name_map,
+            param_env,
+            node,
+            reader_path,
+            builder_path,
+        )?;
+    }
+    print_struct_converters(
+        out,
+        node_map,
+        node_name_map,
+        param_env,
+        node_id,
+        name,
+        has_non_union_fields,
+    )?;
+    let mut dependencies = Vec::new();
+    collect_struct_dependencies(node_map, struct_node, &mut dependencies)?;
+    end_definition(
+        out,
+        node_id,
+        dependencies,
+        has_union && has_non_union_fields,
+    );
+    Ok(())
+}
+
+/// Appends the structs, groups and enums that the fields of struct or group
+/// `struct_node` refer to, except for skipped fields, to `dependencies`. The
+/// fields of groups without a type of their own count as fields of
+/// `struct_node`.
+fn collect_struct_dependencies(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    struct_node: schema_capnp::node::struct_::Reader,
+    dependencies: &mut Vec<u64>,
+) -> Result<()> {
+    for field in struct_node.get_fields()?.iter() {
+        if has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        match field.which()? {
+            schema_capnp::field::Slot(slot) => {
+                collect_type_dependencies(node_map, slot.get_type()?, dependencies)?
+            }
+            schema_capnp::field::Group(group) => {
+                let group_struct_node = get_struct_node(get_node(node_map, group.get_type_id())?)?;
+                let is_union_member = field.get_discriminant_value() != NO_DISCRIMINANT;
+                if (is_union_member && is_inline_group(group_struct_node))
+                    || is_inlined(field, group_struct_node)?
+                {
+                    collect_struct_dependencies(node_map, group_struct_node, dependencies)?;
+                } else {
+                    dependencies.push(group.get_type_id());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Appends the structs and enums that `type_` refers to, including through
+/// its generic arguments, to `dependencies`.
+fn collect_type_dependencies(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    type_: schema_capnp::type_::Reader,
+    dependencies: &mut Vec<u64>,
+) -> Result<()> {
+    match type_.which()? {
+        schema_capnp::type_::List(list) => {
+            collect_type_dependencies(node_map, list.get_element_type()?, dependencies)?
+        }
+        schema_capnp::type_::Enum(enum_) => dependencies.push(enum_.get_type_id()),
+        schema_capnp::type_::Struct(struct_) => {
+            dependencies.push(struct_.get_type_id());
+            for argument in brand_arguments(node_map, struct_)? {
+                if let BrandArgument::Type(type_) = argument {
+                    collect_type_dependencies(node_map, type_, dependencies)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Returns whether a group that is a union member is represented as an inline
+/// record; a group that contains a union gets a type of its own instead.
+fn is_inline_group(group_struct_node: schema_capnp::node::struct_::Reader) -> bool {
+    group_struct_node.get_discriminant_count() == 0
+}
+
+/// Prints the types, decoders and encoders of the groups in `struct_node`,
+/// at any depth, except for groups that are represented as inline records.
+fn print_group_types(
+    out: &mut Output,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    struct_node: schema_capnp::node::struct_::Reader,
+    reader_path: &str,
+    builder_path: &str,
+) -> Result<()> {
+    for field in struct_node.get_fields()?.iter() {
+        let schema_capnp::field::Group(group) = field.which()? else {
+            continue;
+        };
+        if has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        let capitalized_name = capitalize(field.get_name()?.to_str()?)?;
+        let group_reader_path = format!("{}.{}", reader_path, capitalized_name);
+        let group_builder_path = format!("{}.{}", builder_path, capitalized_name);
+        let group_node = get_node(node_map, group.get_type_id())?;
+        let group_struct_node = get_struct_node(group_node)?;
+        let is_union_member = field.get_discriminant_value() != NO_DISCRIMINANT;
+        if (is_union_member && is_inline_group(group_struct_node))
+            || is_inlined(field, group_struct_node)?
+        {
+            print_group_types(
+                out,
+                node_map,
+                node_name_map,
+                param_env,
+                group_struct_node,
+                &group_reader_path,
+                &group_builder_path,
+            )
+            .map_err(|error| error.in_node(group_node))?;
+        } else {
+            print_struct(
+                out,
+                node_map,
+                node_name_map,
+                param_env,
+                group.get_type_id(),
+                &group_reader_path,
+                &group_builder_path,
+            )
+            .map_err(|error| error.in_node(group_node))?;
+        }
+    }
+    Ok(())
+}
+
+/// A field of a record, which may belong to a group merged into the record,
+/// with its doc comment, the paths of the capnp-ocaml modules that access the
+/// struct or group it belongs to, and the reader and builder of that struct or
+/// group.
+struct RecordField<'a> {
+    field: schema_capnp::field::Reader<'a>,
+    doc: &'a str,
+    reader_path: String,
+    builder_path: String,
+    reader: String,
+    builder: String,
+}
+
+/// Returns whether group field `field`, whose group is `group_struct_node`, is
+/// merged into the record of the enclosing struct, as `$OCaml.inline`
+/// requests.
+fn is_inlined(
+    field: schema_capnp::field::Reader,
+    group_struct_node: schema_capnp::node::struct_::Reader,
+) -> Result<bool> {
+    if !has_annotation(field.get_annotations()?, INLINE_ANNOTATION_ID)? {
+        return Ok(false);
+    }
+    if !is_inline_group(group_struct_node) {
+        return Err(Error::new("groups that have a union cannot be inlined")
+            .in_member(field.get_name()?.to_str()?));
+    }
+    Ok(true)
+}
+
+/// Appends the fields of struct or group `node_id` that are not part of its
+/// union to `record_fields`, leaving out skipped fields and replacing inlined
+/// groups by their fields.
+#[allow(clippy::too_many_arguments)]
+fn collect_record_fields<'a>(
+    node_map: &HashMap<u64, schema_capnp::node::Reader<'a>>,
+    docs: &HashMap<u64, schema_capnp::node::source_info::Reader<'a>>,
+    node_id: u64,
+    reader_path: &str,
+    builder_path: &str,
+    reader: &str,
+    builder: &str,
+    record_fields: &mut Vec<RecordField<'a>>,
+) -> Result<()> {
+    let struct_node = get_struct_node(get_node(node_map, node_id)?)?;
+    for (index, field) in struct_node.get_fields()?.iter().enumerate() {
+        if field.get_discriminant_value() != NO_DISCRIMINANT
+            || has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)?
+        {
+            continue;
+        }
+        if let schema_capnp::field::Group(group) = field.which()? {
+            let group_node = get_node(node_map, group.get_type_id())?;
+            let group_struct_node = get_struct_node(group_node)?;
+            if is_inlined(field, group_struct_node)? {
+                let name = field.get_name()?.to_str()?;
+                let snake_name = pascal_to_snake(name);
+                let capitalized_name = capitalize(name)?;
+                collect_record_fields(
+                    node_map,
+                    docs,
+                    group.get_type_id(),
+                    &format!("{}.{}", reader_path, capitalized_name),
+                    &format!("{}.{}", builder_path, capitalized_name),
+                    &format!("({}.{}_get {})", reader_path, snake_name, reader),
+                    &format!("({}.{}_get {})", builder_path, snake_name, builder),
+                    record_fields,
+                )?;
+                continue;
+            }
+        }
+        record_fields.push(RecordField {
+            field,
+            doc: member_doc(docs, node_id, index as u32)?,
+            reader_path: reader_path.to_string(),
+            builder_path: builder_path.to_string(),
+            reader: reader.to_string(),
+            builder: builder.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Prints the record type, decoder and encoder bodies for the fields of struct
+/// or group `node_id` that are not part of its union. If `inline` is set, the
+/// record is printed on one line, reading from `r'` and writing to `b'`, as is
+/// done for groups that are union members.
+#[allow(clippy::too_many_arguments)]
+fn print_record(
+    out: &mut Output,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    node_id: u64,
+    name: &str,
+    reader_path: &str,
+    builder_path: &str,
+    inline: bool,
+) -> Result<()> {
+    let struct_node = get_struct_node(get_node(node_map, node_id)?)?;
+    let (reader, builder, value) = if inline {
+        ("r'", "b'", "v'")
+    } else {
+        ("r", "b", "v")
+    };
+    let field_separator = if inline { "" } else { "\n    " };
+    let statement_separator = if inline { " " } else { "\n  " };
+    // In result mode, the decoder binds the fields whose decoding can fail
+    // before the record; the bindings are inserted at `record_start`.
+    let record_start = out.decoder.len();
+    let mut bindings = String::new();
+    if inline {
+        write!(out.types, "{{").unwrap();
+        if out.options.result {
+            write!(out.decoder, "Ok ({} {{", name).unwrap();
+        } else {
+            write!(out.decoder, "{} {{", name).unwrap();
+        }
+    } else {
+        write!(out.types, "  {{").unwrap();
+        write!(out.decoder, "  {}{{", ok(&out.options, "")).unwrap();
+    }
+    let mut is_first_field = true;
+    // The doc comment of a field follows it, after the separator.
+    let mut previous_doc = String::new();
+    let mut print_separators = |out: &mut Output, previous_doc: &str| {
+        if is_first_field {
+            is_first_field = false;
+        } else {
+            write!(out.types, ";").unwrap();
+            if !previous_doc.is_empty() {
+                write!(out.types, " {}", previous_doc).unwrap();
+            }
+            if inline {
+                write!(out.types, " ").unwrap();
+                write!(out.decoder, "; ").unwrap();
+            } else {
+                write!(out.decoder, ";").unwrap();
+            }
+            write!(out.encoder, ";").unwrap();
+        }
+        write!(out.types, "{}", field_separator).unwrap();
+        write!(out.decoder, "{}", field_separator).unwrap();
+        write!(out.encoder, "{}", statement_separator).unwrap();
+    };
+    let mut record_fields = Vec::new();
+    collect_record_fields(
+        node_map,
+        out.docs,
+        node_id,
+        reader_path,
+        builder_path,
+        reader,
+        builder,
+        &mut record_fields,
+    )?;
+    if inline && record_fields.is_empty() {
+        return Err(Error::new("all fields of this group are skipped"));
+    }
+    for record_field in &record_fields {
+        let field = record_field.field;
+        let reader_path = &record_field.reader_path;
+        let builder_path = &record_field.builder_path;
+        let reader = &record_field.reader;
+        let builder = &record_field.builder;
+        let name = field.get_name()?.to_str()?;
+        let label = field_label(node_map, field).map_err(|error| error.in_member(name))?;
+        match field.which()? {
+            schema_capnp::field::Slot(slot) => {
+                print_separators(out, &previous_doc);
+                let snake_name = pascal_to_snake(name);
+                write!(out.types, "{}: ", label).unwrap();
+                let type_ = slot.get_type()?;
+                let repr = FieldRepr::of_field(&out.options, field)
+                    .map_err(|error| error.in_member(name))?;
+                print_field_type(
+                    &mut out.types,
+                    &out.options,
+                    node_map,
+                    node_name_map,
+                    Some(param_env),
+                    type_,
+                    repr,
+                )
+                .map_err(|error| error.in_member(name))?;
+                let is_optional = is_optional(&out.options, slot)?;
+                if is_optional {
+                    write!(out.types, " option").unwrap();
+                }
+                let mut decoding = String::new();
+                print_field_type_decoding(
+                    &mut decoding,
+                    &out.options,
+                    node_map,
+                    node_name_map,
+                    Some(param_env),
+                    type_,
+                    &format!("({}.{}_get {})", reader_path, snake_name, reader),
+                    repr,
+                )
+                .map_err(|error| error.in_member(name))?;
+                let is_fallible = repr.is_fallible(&out.options, type_)?;
+                if is_optional {
+                    let some = if is_fallible {
+                        format!("Result.map Option.some ({})", decoding)
+                    } else if decoding.starts_with('(') {
+                        format!("Some {}", decoding)
+                    } else {
+                        format!("Some ({})", decoding)
+                    };
+                    let none = if is_fallible { "Ok None" } else { "None" };
+                    decoding = format!(
+                        "(if {}.has_{} {} then {} else {})",
+                        reader_path, snake_name, reader, some, none
+                    );
+                }
+                print_field_decoding(
+                    &mut out.decoder,
+                    &mut bindings,
+                    statement_separator,
+                    &label,
+                    Some(name),
+                    &decoding,
+                    is_fallible,
+                );
+                let field_value = format!("{}.{}", value, label);
+                if is_optional {
+                    write!(out.encoder, "(match {} with Some v' -> ", field_value).unwrap();
+                }
+                print_field_type_encoding(
+                    &mut out.encoder,
+                    &out.options,
+                    node_map,
+                    node_name_map,
+                    Some(param_env),
+                    type_,
+                    builder_path,
+                    builder,
+                    &snake_name,
+                    if is_optional { "v'" } else { &field_value },
+                    repr,
+                )
+                .map_err(|error| error.in_member(name))?;
+                if is_optional {
+                    write!(out.encoder, " | None -> ())").unwrap();
+                }
+            }
+            schema_capnp::field::Group(group) => {
+                print_separators(out, &previous_doc);
+                let snake_name = pascal_to_snake(name);
+                let group_name = get_name(node_name_map, group.get_type_id())?;
+                write!(
+                    out.types,
+                    "{}: {}{}",
+                    label,
+                    type_args(param_env),
+                    escape_keyword(group_name.to_string())
+                )
+                .unwrap();
+                print_field_decoding(
+                    &mut out.decoder,
+                    &mut bindings,
+                    statement_separator,
+                    &label,
+                    Some(name),
+                    &format!(
+                        "decode_{}{} ({}.{}_get {})",
+                        group_name,
+                        fun_args(param_env, "decode"),
+                        reader_path,
+                        snake_name,
+                        reader
+                    ),
+                    out.options.result,
+                );
+                write!(
+                    out.encoder,
+                    "encode_{}{} ({}.{}_get {}) {}.{}",
+                    group_name,
+                    fun_args(param_env, "encode"),
+                    builder_path,
+                    snake_name,
+                    builder,
+                    value,
+                    label
+                )
+                .unwrap();
+            }
+        }
+        previous_doc = doc_comment(record_field.doc, "    ");
+    }
+    if struct_node.get_discriminant_count() > 0 && !inline {
+        print_separators(out, &previous_doc);
+        previous_doc.clear();
+        write!(
+            out.types,
+            "union: {}{}",
+            type_args(param_env),
+            escape_keyword(format!("{}_union", name))
+        )
+        .unwrap();
+        print_field_decoding(
+            &mut out.decoder,
+            &mut bindings,
+            statement_separator,
+            "union",
+            None,
+            &format!(
+                "decode_{}_union{} {}",
+                name,
+                fun_args(param_env, "decode"),
+                reader
+            ),
+            out.options.result,
+        );
+        write!(
+            out.encoder,
+            "encode_{}_union{} {} {}.union",
+            name,
+            fun_args(param_env, "encode"),
+            builder,
+            value
+        )
+        .unwrap();
+    }
+    if !previous_doc.is_empty() {
+        write!(out.types, " {}", previous_doc).unwrap();
+    }
+    if inline {
+        write!(out.types, "}}").unwrap();
+        if out.options.result {
+            write!(out.decoder, "}})").unwrap();
+        } else {
+            write!(out.decoder, "}}").unwrap();
+        }
+    } else {
+        write!(out.types, "\n  }}").unwrap();
+        write!(out.decoder, "\n  }}").unwrap();
+        if !bindings.is_empty() {
+            bindings.insert_str(0, "  ");
+            bindings.truncate(bindings.len() - 2);
+        }
+    }
+    out.decoder.insert_str(record_start, &bindings);
+    Ok(())
+}
+
+/// Writes the decoding of record field `field_name`, named `member` in the
+/// schema, to `decoder`. If the decoding is `fallible`, it is bound to a
+/// variable by a `let*` appended to `bindings`, each followed by `separator`,
+/// and the field takes that variable.
+fn print_field_decoding(
+    decoder: &mut String,
+    bindings: &mut String,
+    separator: &str,
+    field_name: &str,
+    member: Option<&str>,
+    decoding: &str,
+    fallible: bool,
+) {
+    if fallible {
+        write!(bindings, "let* f_{} = ", field_name).unwrap();
+        match member {
+            Some(member) => write!(bindings, "in_field \"{}\" ({})", member, decoding).unwrap(),
+            None => write!(bindings, "{}", decoding).unwrap(),
+        }
+        write!(bindings, " in{}", separator).unwrap();
+        write!(decoder, "{} = f_{}", field_name, field_name).unwrap();
+    } else {
+        write!(decoder, "{} = {}", field_name, decoding).unwrap();
+    }
+}
+
+/// Prints the variant type, decoder and encoder bodies for the union of
+/// struct (or group) `node`.
+fn print_union(
+    out: &mut Output,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: &ParamEnv<'_>,
+    node: schema_capnp::node::Reader,
+    reader_path: &str,
+    builder_path: &str,
+) -> Result<()> {
+    let struct_node = get_struct_node(node)?;
+    writeln!(out.decoder, "  match {}.get r with", reader_path).unwrap();
+    write!(out.encoder, "\n  match v with").unwrap();
+    let mut is_first_variant = true;
+    // The doc comment of a constructor follows it, at the end of its line.
+    let mut previous_doc = String::new();
+    let fields = struct_node.get_fields()?;
+    for (index, field) in fields.iter().enumerate() {
+        if field.get_discriminant_value() == NO_DISCRIMINANT {
+            continue;
+        }
+        if is_first_variant {
+            is_first_variant = false;
+        } else {
+            writeln!(out.types, "{}", previous_doc).unwrap();
+        }
+        let doc = doc_comment(member_doc(out.docs, node.get_id(), index as u32)?, "  ");
+        previous_doc = if doc.is_empty() {
+            doc
+        } else {
+            format!(" {}", doc)
+        };
+        let name = field.get_name()?.to_str()?;
+        let capitalized_name = capitalize(name)?;
+        let constructor =
+            member_constructor(node_map, field).map_err(|error| error.in_member(name))?;
+        if has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            return Err(Error::new("union members cannot be skipped").in_member(name));
+        }
+        if out.options.unknown && constructor == "Unknown" {
+            return Err(Error::new(
+                "this union member clashes with the `Unknown` constructor added by --unknown",
+            )
+            .in_member(name));
+        }
+        let snake_name = pascal_to_snake(name);
+        write!(out.types, "  | {}", constructor).unwrap();
+        write!(out.decoder, "  | {}", capitalized_name).unwrap();
+        write!(out.encoder, "\n  | {}", constructor).unwrap();
+        match field.which()? {
+            schema_capnp::field::Slot(slot) => {
+                let type_ = slot.get_type()?;
+                if let schema_capnp::type_::Void(()) = type_.which()? {
+                    writeln!(out.decoder, " -> {}", ok(&out.options, &constructor)).unwrap();
+                    write!(out.encoder, " -> {}.{}_set b", builder_path, snake_name).unwrap();
+                } else {
+                    write!(out.types, " of ").unwrap();
+                    let repr = FieldRepr::of_field(&out.options, field)
+                        .map_err(|error| error.in_member(name))?;
+                    print_field_type(
+                        &mut out.types,
+                        &out.options,
+                        node_map,
+                        node_name_map,
+                        Some(param_env),
+                        type_,
+                        repr,
+                    )
+                    .map_err(|error| error.in_member(name))?;
+                    let mut decoding = String::new();
+                    print_field_type_decoding(
+                        &mut decoding,
+                        &out.options,
+                        node_map,
+                        node_name_map,
+                        Some(param_env),
+                        type_,
+                        "r'",
+                        repr,
+                    )
+                    .map_err(|error| error.in_member(name))?;
+                    if repr.is_fallible(&out.options, type_)? {
+                        writeln!(
+                            out.decoder,
+                            " r' -> let* x = in_field \"{}\" ({}) in Ok ({} x)",
+                            name, decoding, constructor
+                        )
+                        .unwrap();
+                    } else {
+                        let variant = format!("{} ({})", constructor, decoding);
+                        if out.options.result {
+                            writeln!(out.decoder, " r' -> Ok ({})", variant).unwrap();
+                        } else {
+                            writeln!(out.decoder, " r' -> {}", variant).unwrap();
+                        }
+                    }
+                    write!(out.encoder, " v' -> ").unwrap();
+                    print_field_type_encoding(
+                        &mut out.encoder,
+                        &out.options,
+                        node_map,
+                        node_name_map,
+                        Some(param_env),
+                        type_,
+                        builder_path,
+                        "b",
+                        &snake_name,
+                        "v'",
+                        repr,
+                    )
+                    .map_err(|error| error.in_member(name))?;
+                }
+            }
+            schema_capnp::field::Group(group) => {
+                let group_node = get_node(node_map, group.get_type_id())?;
+                let group_struct_node = get_struct_node(group_node)?;
+                // Union members that are groups are inline already, unless they
+                // have a union, which `is_inlined` rejects.
+                is_inlined(field, group_struct_node)?;
+                if !is_inline_group(group_struct_node) {
+                    let group_name = get_name(node_name_map, group.get_type_id())?;
+                    write!(
+                        out.types,
+                        " of {}{}",
+                        type_args(param_env),
+                        escape_keyword(group_name.to_string())
+                    )
+                    .unwrap();
+                    if out.options.result {
+                        writeln!(
+                            out.decoder,
+                            " r' -> let* x = in_field \"{}\" (decode_{}{} r') in Ok ({} x)",
+                            name,
+                            group_name,
+                            fun_args(param_env, "decode"),
+                            constructor
+                        )
+                        .unwrap();
+                    } else {
+                        writeln!(
+                            out.decoder,
+                            " r' -> {} (decode_{}{} r')",
+                            constructor,
+                            group_name,
+                            fun_args(param_env, "decode")
+                        )
+                        .unwrap();
+                    }
+                    write!(
+                        out.encoder,
+                        " v' -> encode_{}{} ({}.{}_init b) v'",
+                        group_name,
+                        fun_args(param_env, "encode"),
+                        builder_path,
+                        snake_name
+                    )
+                    .unwrap();
+                    continue;
+                }
+                write!(out.types, " of ").unwrap();
+                if out.options.result {
+                    write!(out.decoder, " r' -> in_field \"{}\" (", name).unwrap();
+                } else {
+                    write!(out.decoder, " r' -> ").unwrap();
+                }
+                write!(
+                    out.encoder,
+                    " v' -> let b' = {}.{}_init b in",
+                    builder_path, snake_name
+                )
+                .unwrap();
+                print_record(
+                    out,
+                    node_map,
+                    node_name_map,
+                    param_env,
+                    group.get_type_id(),
+                    &constructor,
+                    &format!("{}.{}", reader_path, capitalized_name),
+                    &format!("{}.{}", builder_path, capitalized_name),
+                    true,
+                )
+                .map_err(|error| error.in_node(group_node))?;
+                if out.options.result {
+                    write!(out.decoder, ")").unwrap();
+                }
+                writeln!(out.decoder).unwrap();
+            }
+        }
+    }
+    write!(out.types, "{}", previous_doc).unwrap();
+    if out.options.unknown {
+        write!(out.types, "\n  | Unknown of int").unwrap();
+        write!(
+            out.decoder,
+            "  | Undefined n -> {}",
+            unknown_value(&out.options)
+        )
+        .unwrap();
+        // capnp-ocaml's builders offer no way to set a discriminant that is
+        // not in the schema.
+        write!(
+            out.encoder,
+            "\n  | Unknown _ -> invalid_arg \"Unknown discriminant\""
+        )
+        .unwrap();
+    } else if out.options.result {
+        write!(
+            out.decoder,
+            "  | Undefined n -> Error ([], `Undefined_discriminant (\"{}\", n))",
+            display_path(node)?
+        )
+        .unwrap();
+    } else {
+        write!(
+            out.decoder,
+            "  | Undefined _ -> failwith \"Undefined discriminant\""
+        )
+        .unwrap();
+    }
+    Ok(())
+}
+
+/// A type argument of a brand.
+#[derive(Clone, Copy)]
+enum BrandArgument<'a> {
+    /// A type that the brand binds.
+    Type(schema_capnp::type_::Reader<'a>),
+    /// Parameter `index` of scope `scope_id`, which the brand inherits from
+    /// the generic type that uses it.
+    Inherited(u64, u16),
+    /// A parameter that the brand leaves unbound, which stands for any pointer.
+    Unbound,
+}
+
+/// Returns the type arguments that the brand of struct type `struct_` binds,
+/// in the order of the type parameters: those of the outermost scope first.
+/// The parameters of a scope that the brand does not mention are unbound.
+fn brand_arguments<'a>(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    struct_: schema_capnp::type_::struct_::Reader<'a>,
+) -> Result<Vec<BrandArgument<'a>>> {
+    // The generic scopes of the struct, innermost first.
+    let mut generic_scopes = Vec::new();
+    let mut node = get_node(node_map, struct_.get_type_id())?;
+    loop {
+        if node.has_parameters() {
+            generic_scopes.push((node.get_id(), node.get_parameters()?.len()));
+        }
+        match node_map.get(&node.get_scope_id()) {
+            Some(parent) if !matches!(parent.which()?, schema_capnp::node::File(())) => {
+                node = *parent;
+            }
+            _ => break,
+        }
+    }
+    let scopes = if struct_.has_brand() {
+        Some(struct_.get_brand()?.get_scopes()?)
+    } else {
+        None
+    };
+    let mut arguments = Vec::new();
+    for (scope_id, parameter_count) in generic_scopes.into_iter().rev() {
+        let scope = scopes
+            .into_iter()
+            .flatten()
+            .find(|scope| scope.get_scope_id() == scope_id);
+        let Some(scope) = scope else {
+            arguments.extend((0..parameter_count).map(|_| BrandArgument::Unbound));
+            continue;
+        };
+        match scope.which()? {
+            schema_capnp::brand::scope::Bind(bindings) => {
+                for binding in bindings?.iter() {
+                    arguments.push(match binding.which()? {
+                        schema_capnp::brand::binding::Type(type_) => BrandArgument::Type(type_?),
+                        schema_capnp::brand::binding::Unbound(()) => BrandArgument::Unbound,
+                    });
+                }
+            }
+            schema_capnp::brand::scope::Inherit(()) => {
+                arguments.extend(
+                    (0..parameter_count as u16)
+                        .map(|index| BrandArgument::Inherited(scope_id, index)),
+                );
+            }
+        }
+    }
+    Ok(arguments)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn print_nested_nodes(
+    out: &mut Output,
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node_name_map: &HashMap<u64, String>,
+    param_env: Option<&ParamEnv<'_>>,
+    reader_path: &str,
+    builder_path: &str,
+    nested_nodes: capnp::struct_list::Reader<schema_capnp::node::nested_node::Owned>,
+) -> Result<()> {
+    for nested_node in nested_nodes.iter() {
+        let nested_id = nested_node.get_id();
+        let nested_node_name = nested_node.get_name()?.to_str()?;
+        let nested_reader_path = format!("{}.{}", reader_path, nested_node_name);
+        let nested_builder_path = format!("{}.{}", builder_path, nested_node_name);
+        let nested_node = get_node(node_map, nested_id)?;
+        if has_annotation(nested_node.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        match nested_node.which()? {
+            schema_capnp::node::Struct(struct_node) => {
+                let param_env = node_param_env(nested_node, param_env)
+                    .map_err(|error| error.in_node(nested_node))?;
+                print_nested_nodes(
+                    out,
+                    node_map,
+                    node_name_map,
+                    Some(&param_env),
+                    &nested_reader_path,
+                    &nested_builder_path,
+                    nested_node.get_nested_nodes()?,
+                )?;
+
+                if struct_node.has_fields() {
+                    print_struct(
+                        out,
+                        node_map,
+                        node_name_map,
+                        &param_env,
+                        nested_id,
+                        &nested_reader_path,
+                        &nested_builder_path,
+                    )
+                    .map_err(|error| error.in_node(nested_node))?;
+                    print_default(
+                        out,
+                        &param_env,
+                        get_name(node_name_map, nested_id)?,
+                        &nested_reader_path,
+                    );
+                }
+            }
+            schema_capnp::node::Which::Enum(enum_) => {
+                let doc = node_doc(out.docs, nested_id)?;
+                print_definition_separators(out, doc);
+                print_doc_comment(&mut out.interface, doc);
+                let name = get_name(node_name_map, nested_id)?;
+                let escaped_name = escape_keyword(name.to_string());
+                write!(out.types, "{} =", escaped_name).unwrap();
+                let result_type = decoded_type(&out.options, &escaped_name);
+                write!(
+                    out.decoder,
+                    "decode_{} (r: {}.t): {} = match r with",
+                    name, nested_reader_path, result_type
+                )
+                .unwrap();
+                write!(
+                    out.encoder,
+                    "encode_{} (v: {}): {}.t = match v with",
+                    name, escaped_name, nested_builder_path
+                )
+                .unwrap();
+                writeln!(
+                    out.interface,
+                    "val decode_{}: {}.t -> {}",
+                    name, nested_reader_path, result_type
+                )
+                .unwrap();
+                writeln!(
+                    out.interface,
+                    "val encode_{}: {} -> {}.t",
+                    name, escaped_name, nested_builder_path
+                )
+                .unwrap();
+                let enumerants = enum_.get_enumerants()?;
+                for (index, enumerant) in enumerants.iter().enumerate() {
+                    let name = enumerant.get_name()?.to_str()?;
+                    let capitalized_name = capitalize(name)?;
+                    let constructor = constructor_name(enumerant.get_annotations()?, name)
+                        .map_err(|error| error.in_member(name).in_node(nested_node))?;
+                    if out.options.unknown && constructor == "Unknown" {
+                        return Err(Error::new(
+                            "this enumerant clashes with the `Unknown` constructor added by --unknown",
+                        )
+                        .in_member(name)
+                        .in_node(nested_node));
+                    }
+                    write!(out.types, "\n  | {}", constructor).unwrap();
+                    let doc = doc_comment(member_doc(out.docs, nested_id, index as u32)?, "  ");
+                    if !doc.is_empty() {
+                        write!(out.types, " {}", doc).unwrap();
+                    }
+                    write!(
+                        out.decoder,
+                        "\n  | {} -> {}",
+                        capitalized_name,
+                        ok(&out.options, &constructor)
+                    )
+                    .unwrap();
+                    write!(out.encoder, "\n  | {} -> {}", constructor, capitalized_name).unwrap();
+                }
+                if out.options.unknown {
+                    write!(out.types, "\n  | Unknown of int").unwrap();
+                    write!(
+                        out.decoder,
+                        "\n  | Undefined n -> {}",
+                        unknown_value(&out.options)
+                    )
+                    .unwrap();
+                    write!(out.encoder, "\n  | Unknown n -> Undefined n").unwrap();
+                } else if out.options.result {
+                    write!(
+                        out.decoder,
+                        "\n  | Undefined n -> Error ([], `Undefined_enumerant (\"{}\", n))",
+                        display_path(nested_node)?
+                    )
+                    .unwrap();
+                } else {
+                    write!(
+                        out.decoder,
+                        "\n  | Undefined _ -> failwith \"Undefined enumerant\""
+                    )
+                    .unwrap();
+                }
+                print_enum_converters(out, enum_, name)
+                    .map_err(|error| error.in_node(nested_node))?;
+                end_definition(out, nested_id, Vec::new(), false);
+            }
+            schema_capnp::node::Interface(_) => {
+                // The capability type itself is printed by `print_capability_types`.
+                let param_env = node_param_env(nested_node, param_env)
+                    .map_err(|error| error.in_node(nested_node))?;
+                print_nested_nodes(
+                    out,
+                    node_map,
+                    node_name_map,
+                    Some(&param_env),
+                    &nested_reader_path,
+                    &nested_builder_path,
+                    nested_node.get_nested_nodes()?,
+                )?;
+            }
+            schema_capnp::node::Const(const_) => {
+                let name = escape_keyword(get_name(node_name_map, nested_id)?.to_string());
+                let type_ = const_.get_type()?;
+                let mut type_string = String::new();
+                print_type(
+                    &mut type_string,
+                    &out.options,
+                    node_map,
+                    node_name_map,
+                    None,
+                    type_,
+                )
+                .map_err(|error| error.in_node(nested_node))?;
+                let value = constant_literal(
+                    &out.options,
+                    node_map,
+                    node_name_map,
+                    type_,
+                    const_.get_value()?,
+                )
+                .map_err(|error| error.in_node(nested_node))?;
+                writeln!(out.constants, "let {}: {} = {}", name, type_string, value).unwrap();
+                print_doc_comment(&mut out.interface, node_doc(out.docs, nested_id)?);
+                writeln!(out.interface, "val {}: {}", name, type_string).unwrap();
+            }
+            schema_capnp::node::Annotation(_) => {}
+            schema_capnp::node::File(()) => {
+                return Err(Error::new("unexpected nested file").in_node(nested_node));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds the groups of mutually recursive definitions in `definitions`, the
+/// strongly connected components of their dependency graph, with Tarjan's
+/// algorithm. Each group comes after the groups it depends on, and lists the
+/// positions of its definitions in order.
+fn recursive_groups(definitions: &[Definition]) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        definitions: &'a [Definition],
+        positions: HashMap<u64, usize>,
+        next_index: usize,
+        indices: Vec<Option<usize>>,
+        low_links: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        groups: Vec<Vec<usize>>,
+    }
+
+    fn visit(state: &mut State, position: usize) {
+        let index = state.next_index;
+        state.next_index += 1;
+        state.indices[position] = Some(index);
+        state.low_links[position] = index;
+        state.stack.push(position);
+        state.on_stack[position] = true;
+        let definitions = state.definitions;
+        for dependency in &definitions[position].dependencies {
+            // Dependencies on imported nodes lead out of the file.
+            let Some(&dependency) = state.positions.get(dependency) else {
+                continue;
+            };
+            match state.indices[dependency] {
+                None => {
+                    visit(state, dependency);
+                    state.low_links[position] =
+                        state.low_links[position].min(state.low_links[dependency]);
+                }
+                Some(dependency_index) if state.on_stack[dependency] => {
+                    state.low_links[position] = state.low_links[position].min(dependency_index);
+                }
+                Some(_) => {}
+            }
         }
-        schema_capnp::type_::Text(()) => {
-            print!("string");
+        if state.low_links[position] == index {
+            let mut group = Vec::new();
+            loop {
+                let member = state.stack.pop().unwrap();
+                state.on_stack[member] = false;
+                group.push(member);
+                if member == position {
+                    break;
+                }
+            }
+            group.sort_unstable();
+            state.groups.push(group);
         }
-        schema_capnp::type_::Data(()) => {
-            print!("string");
+    }
+
+    let mut state = State {
+        definitions,
+        positions: definitions
+            .iter()
+            .enumerate()
+            .map(|(position, definition)| (definition.node_id, position))
+            .collect(),
+        next_index: 0,
+        indices: vec![None; definitions.len()],
+        low_links: vec![0; definitions.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; definitions.len()],
+        groups: Vec::new(),
+    };
+    for position in 0..definitions.len() {
+        if state.indices[position].is_none() {
+            visit(&mut state, position);
         }
-        schema_capnp::type_::List(list) => {
-            print_type(node_name_map, param_env, list.get_element_type().unwrap());
-            print!(" list");
+    }
+    state.groups
+}
+
+/// Prints the definitions of all nodes, one recursive group at a time, so
+/// that each group of types is a `type ... and ...` definition and each group
+/// of functions a `let ... and ...` definition, which is a `let rec` only if
+/// the functions call each other.
+fn print_definition_groups(out: &mut Output) {
+    let definitions = std::mem::take(&mut out.definitions);
+    for group in recursive_groups(&definitions) {
+        let is_recursive = group.len() > 1 || {
+            let definition = &definitions[group[0]];
+            definition.dependencies.contains(&definition.node_id)
+        };
+        let function_keyword = |is_recursive| if is_recursive { "let rec " } else { "let " };
+        let codec_keyword = function_keyword(
+            is_recursive
+                || group
+                    .iter()
+                    .any(|&position| definitions[position].has_union_type),
+        );
+        let converter_keyword = function_keyword(is_recursive);
+        if !out.types.is_empty() {
+            write!(out.types, "\n\n").unwrap();
+            write!(out.decoder, "\n\n").unwrap();
+            write!(out.encoder, "\n\n").unwrap();
         }
-        schema_capnp::type_::Enum(enum_) => {
-            print!("{}", escape_keyword(node_name_map.get(&enum_.get_type_id()).unwrap().clone()));
+        if !out.converters.is_empty() {
+            write!(out.converters, "\n\n").unwrap();
+        }
+        for (index, &position) in group.iter().enumerate() {
+            let definition = &definitions[position];
+            if index == 0 {
+                let doc = doc_comment(&definition.doc, "");
+                if !doc.is_empty() {
+                    writeln!(out.types, "{}", doc).unwrap();
+                }
+                write!(out.types, "type ").unwrap();
+                write!(out.decoder, "{}", codec_keyword).unwrap();
+                write!(out.encoder, "{}", codec_keyword).unwrap();
+                if !definition.converters.is_empty() {
+                    write!(out.converters, "{}", converter_keyword).unwrap();
+                }
+            } else {
+                print_deriving_attribute(out);
+                writeln!(out.types).unwrap();
+                print_doc_comment(&mut out.types, &definition.doc);
+                write!(out.types, "and ").unwrap();
+                write!(out.decoder, "\nand ").unwrap();
+                write!(out.encoder, "\nand ").unwrap();
+                if !definition.converters.is_empty() {
+                    write!(out.converters, "\nand ").unwrap();
+                }
+            }
+            out.types.push_str(&definition.types);
+            out.decoder.push_str(&definition.decoder);
+            out.encoder.push_str(&definition.encoder);
+            out.converters.push_str(&definition.converters);
+        }
+        print_deriving_attribute(out);
+    }
+}
+
+/// The error for an untyped pointer that would have to be encoded as a generic
+/// argument or list element: capnp-ocaml can only copy a pointer from a reader
+/// into a builder with the setter of a field.
+const UNTYPED_ENCODING_ERROR: &str = "untyped pointers can only be encoded as fields, not as generic arguments or list elements; bind the parameter to a type or skip the field";
+
+/// The error for an untyped pointer under `--json` or `--sexp`: its value has
+/// no representation in either format.
+const UNTYPED_CONVERSION_ERROR: &str =
+    "untyped pointers cannot be converted to or from JSON or S-expressions; skip the field";
+
+/// The kinds of untyped pointers. Each has an OCaml type of its own that wraps
+/// the pointer that holds the value, which can be decoded once its type is
+/// known.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PointerKind {
+    /// An `AnyPointer`, or a generic parameter that a brand leaves unbound.
+    Any,
+    Struct,
+    List,
+    Capability,
+}
+
+impl PointerKind {
+    /// Returns the kind of the unconstrained `AnyPointer` type `unconstrained`.
+    fn of_unconstrained(
+        unconstrained: schema_capnp::type_::any_pointer::unconstrained::Reader,
+    ) -> Result<PointerKind> {
+        Ok(match unconstrained.which()? {
+            schema_capnp::type_::any_pointer::unconstrained::AnyKind(()) => PointerKind::Any,
+            schema_capnp::type_::any_pointer::unconstrained::Struct(()) => PointerKind::Struct,
+            schema_capnp::type_::any_pointer::unconstrained::List(()) => PointerKind::List,
+            schema_capnp::type_::any_pointer::unconstrained::Capability(()) => {
+                PointerKind::Capability
+            }
+        })
+    }
+
+    /// Returns the kind of `AnyPointer` type `any_pointer`, or `None` for a
+    /// generic parameter.
+    fn of_any_pointer(
+        any_pointer: schema_capnp::type_::any_pointer::Reader,
+    ) -> Result<Option<PointerKind>> {
+        match any_pointer.which()? {
+            schema_capnp::type_::any_pointer::Unconstrained(unconstrained) => {
+                Ok(Some(PointerKind::of_unconstrained(unconstrained)?))
+            }
+            schema_capnp::type_::any_pointer::Parameter(_) => Ok(None),
+            schema_capnp::type_::any_pointer::ImplicitMethodParameter(_) => {
+                Err(Error::new("implicit method parameters are not supported"))
+            }
+        }
+    }
+
+    fn type_name(self) -> &'static str {
+        match self {
+            PointerKind::Any => "any_pointer",
+            PointerKind::Struct => "any_struct",
+            PointerKind::List => "any_list",
+            PointerKind::Capability => "any_capability",
+        }
+    }
+
+    /// Returns the constructor that wraps the pointer; `any_pointer` is the
+    /// pointer itself.
+    fn constructor(self) -> Option<&'static str> {
+        match self {
+            PointerKind::Any => None,
+            PointerKind::Struct => Some("Any_struct"),
+            PointerKind::List => Some("Any_list"),
+            PointerKind::Capability => Some("Any_capability"),
+        }
+    }
+
+    /// Returns the OCaml expression that wraps pointer `pointer`.
+    fn wrap(self, pointer: &str) -> String {
+        match self.constructor() {
+            Some(constructor) => format!("{} {}", constructor, pointer),
+            None => pointer.to_string(),
+        }
+    }
+
+    /// Returns the OCaml expression for the pointer of value `value`.
+    fn unwrap(self, value: &str) -> String {
+        match self.constructor() {
+            Some(constructor) => format!("(let ({} p) = {} in p)", constructor, value),
+            None => value.to_string(),
+        }
+    }
+
+    /// Returns the OCaml pattern that matches a value and binds its pointer to
+    /// `pointer`.
+    fn pattern(self, pointer: &str) -> String {
+        match self.constructor() {
+            Some(constructor) => format!("({} {})", constructor, pointer),
+            None => format!("({}: any_pointer)", pointer),
+        }
+    }
+
+    /// Returns the function that decodes values from the pointers that hold
+    /// them, as a decoder argument of a generic decoder.
+    fn decoder(self, options: &Options) -> String {
+        match self.constructor() {
+            Some(constructor) if options.result => format!("(fun p -> Ok ({} p))", constructor),
+            Some(constructor) => format!("(fun p -> {} p)", constructor),
+            None => format!("(fun p -> {})", ok(options, "p")),
+        }
+    }
+
+    /// Returns the doc comment of the type.
+    fn doc(self) -> &'static str {
+        match self {
+            PointerKind::Any => {
+                "An untyped pointer: an AnyPointer value, or an unbound generic argument."
+            }
+            PointerKind::Struct => {
+                "The value of an AnyStruct field: a pointer to a struct of any type."
+            }
+            PointerKind::List => "The value of an AnyList field: a pointer to a list of any type.",
+            PointerKind::Capability => {
+                "The value of a Capability field: a pointer to a capability of any type."
+            }
+        }
+    }
+}
+
+/// Adds the kinds of the untyped pointers that type `type_` refers to,
+/// including through its generic arguments, to `kinds`.
+fn collect_pointer_kinds(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    type_: schema_capnp::type_::Reader,
+    kinds: &mut BTreeSet<PointerKind>,
+) -> Result<()> {
+    match type_.which()? {
+        schema_capnp::type_::List(list) => {
+            collect_pointer_kinds(node_map, list.get_element_type()?, kinds)?
         }
         schema_capnp::type_::Struct(struct_) => {
-            if struct_.has_brand() {
-                let brand = struct_.get_brand().unwrap();
-                for scope in brand.get_scopes().unwrap().iter() {
-                    //let scope_id = scope.get_scope_id();
-                    match scope.which().unwrap() {
-                        schema_capnp::brand::scope::Bind(bindings) => {
-                            for binding in bindings.unwrap().iter() {
-                                match binding.which().unwrap() {
-                                    schema_capnp::brand::binding::Which::Type(t) => {
-                                        print_type(node_name_map, param_env, t.unwrap());
-                                        print!(" ");
-                                    }
-                                    _ => todo!(),
-                                }
-                            }
-                        }
-                        _ => todo!(),
+            for argument in brand_arguments(node_map, struct_)? {
+                match argument {
+                    BrandArgument::Type(type_) => collect_pointer_kinds(node_map, type_, kinds)?,
+                    BrandArgument::Inherited(..) => {}
+                    BrandArgument::Unbound => {
+                        kinds.insert(PointerKind::Any);
                     }
                 }
             }
-            print!("{}", escape_keyword(node_name_map.get(&struct_.get_type_id()).unwrap().clone()));
         }
-        schema_capnp::type_::Interface(interface) => {
-            print!("{}", interface.get_type_id());
+        schema_capnp::type_::AnyPointer(any_pointer) => {
+            kinds.extend(PointerKind::of_any_pointer(any_pointer)?);
         }
-        schema_capnp::type_::AnyPointer(any_pointer) => match any_pointer.which().unwrap() {
-            schema_capnp::type_::any_pointer::Parameter(parameter) => {
-                print!(
-                    "'{}",
-                    param_env.unwrap().get(parameter.get_scope_id())
-                        [parameter.get_parameter_index() as usize]
-                );
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Adds the kinds of the untyped pointers that the fields of struct or group
+/// `node`, or of its groups, refer to to `kinds`.
+fn collect_struct_pointer_kinds(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node: schema_capnp::node::Reader,
+    kinds: &mut BTreeSet<PointerKind>,
+) -> Result<()> {
+    for field in get_struct_node(node)?.get_fields()?.iter() {
+        if has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        match field.which()? {
+            schema_capnp::field::Slot(slot) => {
+                collect_pointer_kinds(node_map, slot.get_type()?, kinds)?
             }
-            _ => todo!(),
-        },
+            schema_capnp::field::Group(group) => collect_struct_pointer_kinds(
+                node_map,
+                get_node(node_map, group.get_type_id())?,
+                kinds,
+            )?,
+        }
     }
+    Ok(())
 }
 
-fn enter_nested_nodes(
+/// Adds the kinds of the untyped pointers that the structs and constants among
+/// `nested_nodes`, or nested in them, refer to to `kinds`.
+fn collect_nested_pointer_kinds(
     node_map: &HashMap<u64, schema_capnp::node::Reader>,
-    node_name_map: &mut HashMap<u64, String>,
-    qualifier: &str,
     nested_nodes: capnp::struct_list::Reader<schema_capnp::node::nested_node::Owned>,
-) {
+    kinds: &mut BTreeSet<PointerKind>,
+) -> Result<()> {
     for nested_node in nested_nodes.iter() {
-        let nested_id = nested_node.get_id();
-        let nested_name = pascal_to_snake(
-            nested_node.get_name().unwrap().to_str().unwrap(),
-        );
-        let nested_qualifier = if qualifier.is_empty() {
-            nested_name.to_string()
-        } else {
-            format!("{}_{}", qualifier, nested_name)
+        let node = get_node(node_map, nested_node.get_id())?;
+        if has_annotation(node.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        match node.which()? {
+            schema_capnp::node::Struct(_) => collect_struct_pointer_kinds(node_map, node, kinds)?,
+            schema_capnp::node::Const(const_) => {
+                collect_pointer_kinds(node_map, const_.get_type()?, kinds)?
+            }
+            _ => {}
+        }
+        collect_nested_pointer_kinds(node_map, node.get_nested_nodes()?, kinds)?;
+    }
+    Ok(())
+}
+
+/// Returns whether `predicate` holds for any slot field of struct or group
+/// `node`, or of its groups.
+fn struct_has_slot(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    node: schema_capnp::node::Reader,
+    predicate: &impl Fn(schema_capnp::field::Reader, schema_capnp::type_::Reader) -> Result<bool>,
+) -> Result<bool> {
+    for field in get_struct_node(node)?.get_fields()?.iter() {
+        if has_annotation(field.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        let holds = match field.which()? {
+            schema_capnp::field::Slot(slot) => predicate(field, slot.get_type()?)?,
+            schema_capnp::field::Group(group) => struct_has_slot(
+                node_map,
+                get_node(node_map, group.get_type_id())?,
+                predicate,
+            )?,
         };
-        node_name_map.insert(nested_id, nested_qualifier.clone());
-        let node = node_map.get(&nested_id).unwrap();
-        enter_nested_nodes(
-            node_map,
-            node_name_map,
-            &nested_qualifier,
-            node.get_nested_nodes().unwrap(),
-        );
+        if holds {
+            return Ok(true);
+        }
     }
+    Ok(false)
 }
 
-fn print_nested_nodes(
-    decoder: &mut String,
+/// Returns whether `predicate` holds for any slot field of the structs among
+/// `nested_nodes`, or nested in them.
+fn nested_have_slot(
+    node_map: &HashMap<u64, schema_capnp::node::Reader>,
+    nested_nodes: capnp::struct_list::Reader<schema_capnp::node::nested_node::Owned>,
+    predicate: &impl Fn(schema_capnp::field::Reader, schema_capnp::type_::Reader) -> Result<bool>,
+) -> Result<bool> {
+    for nested_node in nested_nodes.iter() {
+        let node = get_node(node_map, nested_node.get_id())?;
+        if has_annotation(node.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        if let schema_capnp::node::Struct(_) = node.which()? {
+            if struct_has_slot(node_map, node, predicate)? {
+                return Ok(true);
+            }
+        }
+        if nested_have_slot(node_map, node.get_nested_nodes()?, predicate)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Prints the types of untyped pointers of `kinds`, along with the functions
+/// the show, eq and ord derivers expect for them and the functions that decode
+/// their values once their types are known.
+fn print_pointer_types(out: &mut Output, kinds: &BTreeSet<PointerKind>) {
+    for &kind in kinds {
+        let name = kind.type_name();
+        print_doc_comment(&mut out.types, kind.doc());
+        match kind.constructor() {
+            Some(constructor) => writeln!(
+                out.types,
+                "type {} = {} of R.pointer_t [@@unboxed]",
+                name, constructor
+            )
+            .unwrap(),
+            None => writeln!(out.types, "type {} = R.pointer_t", name).unwrap(),
+        }
+        // Like capabilities, untyped pointers are opaque to the derived
+        // functions.
+        if out.options.derives("show") {
+            writeln!(
+                out.deriving_helpers,
+                "let pp_{} fmt (_: {}) = Format.pp_print_string fmt \"<pointer>\" [@@ocaml.warning \"-32\"]",
+                name, name
+            )
+            .unwrap();
+        }
+        if out.options.derives("eq") {
+            writeln!(
+                out.deriving_helpers,
+                "let equal_{} (_: {}) (_: {}) = true [@@ocaml.warning \"-32\"]",
+                name, name, name
+            )
+            .unwrap();
+        }
+        if out.options.derives("ord") {
+            writeln!(
+                out.deriving_helpers,
+                "let compare_{} (_: {}) (_: {}) = 0 [@@ocaml.warning \"-32\"]",
+                name, name, name
+            )
+            .unwrap();
+        }
+        // A struct is decoded from the struct the pointer points to, like the
+        // root of a message, and the other kinds from the pointer itself, like
+        // generic arguments.
+        if kind == PointerKind::Struct {
+            writeln!(
+                out.pointer_helpers,
+                "let {}_as decode {} = decode (R.of_pointer p)",
+                name,
+                kind.pattern("p")
+            )
+            .unwrap();
+            writeln!(
+                out.interface,
+                "val {}_as: ('a S.reader_t -> 't) -> {} -> 't",
+                name, name
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                out.pointer_helpers,
+                "let {}_as decode {} = decode p",
+                name,
+                kind.pattern("p")
+            )
+            .unwrap();
+            writeln!(
+                out.interface,
+                "val {}_as: (R.pointer_t -> 't) -> {} -> 't",
+                name, name
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Returns the definition of the type `data` that represents `Data` values
+/// with `--data=private`, and of the function making one from a string. The
+/// type is private in the interface, so that other modules can read the
+/// strings but only make them explicitly.
+fn data_type(options: &Options, interface: bool) -> String {
+    let mut code = String::new();
+    print_doc_comment(
+        &mut code,
+        "The value of a Data field: binary data, which need not be text.",
+    );
+    write!(
+        code,
+        "type data = {}string",
+        if interface { "private " } else { "" }
+    )
+    .unwrap();
+    if !options.deriving.is_empty() {
+        write!(code, "\n[@@deriving {}]", options.deriving.join(", ")).unwrap();
+    }
+    writeln!(code).unwrap();
+    writeln!(code).unwrap();
+    if interface {
+        writeln!(code, "val data_of_string: string -> data").unwrap();
+    } else {
+        writeln!(code, "let data_of_string s = s").unwrap();
+    }
+    code
+}
+
+/// Prints a type abbreviation for the capability type of each interface,
+/// so that interface-typed fields can refer to it by name, along with the
+/// functions the show, eq and ord derivers expect for it.
+fn print_capability_types(
+    out: &mut Output,
     node_map: &HashMap<u64, schema_capnp::node::Reader>,
     node_name_map: &HashMap<u64, String>,
-    is_first_type: &mut bool,
     reader_path: &str,
     nested_nodes: capnp::struct_list::Reader<schema_capnp::node::nested_node::Owned>,
-) {
+) -> Result<()> {
     for nested_node in nested_nodes.iter() {
         let nested_id = nested_node.get_id();
-        let nested_node_name = nested_node.get_name().unwrap().to_str().unwrap();
+        let nested_node_name = nested_node.get_name()?.to_str()?;
         let nested_reader_path = format!("{}.{}", reader_path, nested_node_name);
-        let nested_node = node_map.get(&nested_id).unwrap();
-        match nested_node.which().unwrap() {
-            schema_capnp::node::Struct(struct_node) => {
-                print_nested_nodes(
-                    decoder,
-                    node_map,
-                    node_name_map,
-                    is_first_type,
-                    &nested_reader_path,
-                    nested_node.get_nested_nodes().unwrap(),
-                );
-
-                if struct_node.has_fields() {
-                    if *is_first_type {
-                        *is_first_type = false;
-                        print!("type ");
-                        write!(decoder, "let rec ").unwrap();
-                    } else {
-                        print!("\nand ");
-                        write!(decoder, "\nand ").unwrap();
-                    }
-                    let name = node_name_map.get(&nested_id).unwrap();
-                    let escaped_name = escape_keyword(name.clone());
-                    write!(decoder, "decode_{}", name).unwrap();
-                    let mut param_env = ParamEnv {
-                        scope_id: nested_id,
-                        parameters: Vec::new(),
-                        parent_env: None,
-                    };
-                    if nested_node.has_parameters() {
-                        let mut generic_args = String::new();
-                        let mut fun_param_types = String::new();
-                        let mut fun_args = String::new();
-                        write!(decoder, ":").unwrap();
-                        let params = nested_node.get_parameters().unwrap();
-                        for param in params.iter() {
-                            let name = pascal_to_snake(param.get_name().unwrap().to_str().unwrap());
-                            print!("'{} ", name);
-                            write!(generic_args, "'{} ", name).unwrap();
-                            write!(decoder, " 'r{} '{}", name, name).unwrap();
-                            write!(fun_param_types, "('r{} S.reader_t -> '{}) -> ", name, name)
-                                .unwrap();
-                            write!(fun_args, " decode_{}", name).unwrap();
-
-                            param_env.parameters.push(name);
-                        }
-                        print!("{} =\n", escaped_name);
-                        write!(
-                            decoder,
-                            ". {}{}.t -> {}{} = fun{} r ->\n",
-                            fun_param_types, nested_reader_path, generic_args, escaped_name, fun_args
-                        )
-                        .unwrap();
-                    } else {
-                        print!("{} =\n", escaped_name);
-                        write!(decoder, " r: {} =\n", escaped_name).unwrap();
-                    }
-
-                    let discriminant_count = struct_node.get_discriminant_count();
-                    if discriminant_count > 0 {
-                        write!(decoder, "  match {}.get r with\n", nested_reader_path).unwrap();
-                        let mut is_first_variant = true;
-                        let fields = struct_node.get_fields().unwrap();
-                        for field in fields.iter() {
-                            if is_first_variant {
-                                is_first_variant = false;
-                            } else {
-                                println!();
-                            }
-                            assert!(field.get_discriminant_value() != NO_DISCRIMINANT);
-                            let name = field.get_name().unwrap().to_str().unwrap();
-                            let capitalized_name = name
-                                .chars()
-                                .next()
-                                .unwrap()
-                                .to_uppercase()
-                                .collect::<String>()
-                                + &name[1..];
-                            print!("  | {}", capitalized_name);
-                            write!(decoder, "  | {}", capitalized_name).unwrap();
-                            match field.which().unwrap() {
-                                schema_capnp::field::Slot(slot) => {
-                                    let type_ = slot.get_type().unwrap();
-                                    if let schema_capnp::type_::Void(()) = type_.which().unwrap() {
-                                        write!(decoder, " -> {}\n", capitalized_name).unwrap();
-                                    } else {
-                                        print!(" of ");
-                                        print_type(&node_name_map, Some(&param_env), type_);
-                                        write!(decoder, " r' -> {} (", capitalized_name).unwrap();
-                                        print_type_decoding(
-                                            decoder,
-                                            &node_name_map,
-                                            Some(&param_env),
-                                            type_,
-                                            "r'",
-                                        );
-                                        write!(decoder, ")\n").unwrap();
-                                    }
-                                }
-                                schema_capnp::field::Group(group) => {
-                                    let type_ = group.get_type_id();
-                                    let group_node = node_map.get(&type_).unwrap();
-                                    match group_node.which().unwrap() {
-                                        schema_capnp::node::Struct(struct_node) => {
-                                            print!(" of {{");
-                                            write!(decoder, " r' -> {} {{", capitalized_name)
-                                                .unwrap();
-                                            let mut is_first_field = true;
-                                            let fields = struct_node.get_fields().unwrap();
-                                            for field in fields.iter() {
-                                                let name =
-                                                    field.get_name().unwrap().to_str().unwrap();
-                                                match field.which().unwrap() {
-                                                    schema_capnp::field::Slot(slot) => {
-                                                        if is_first_field {
-                                                            is_first_field = false;
-                                                        } else {
-                                                            print!("; ");
-                                                            write!(decoder, "; ").unwrap();
-                                                        }
-                                                        let snake_name = pascal_to_snake(name);
-                                                        let escaped_snake_name =
-                                                            escape_keyword(snake_name.clone());
-                                                        print!("{}: ", escaped_snake_name);
-                                                        write!(
-                                                            decoder,
-                                                            "{} = ",
-                                                            escaped_snake_name
-                                                        )
-                                                        .unwrap();
-                                                        let type_ = slot.get_type().unwrap();
-                                                        print_type(
-                                                            &node_name_map,
-                                                            Some(&param_env),
-                                                            type_,
-                                                        );
-                                                        print_type_decoding(
-                                                            decoder,
-                                                            &node_name_map,
-                                                            Some(&param_env),
-                                                            type_,
-                                                            &format!(
-                                                                "({}.{}.{}_get r')",
-                                                                nested_reader_path,
-                                                                capitalized_name,
-                                                                snake_name
-                                                            ),
-                                                        );
-                                                    }
-                                                    _ => {}
-                                                }
-                                            }
-                                            print!("}}");
-                                            write!(decoder, "}}").unwrap();
-                                        }
-                                        _ => todo!(),
-                                    }
-                                }
-                            }
-                        }
-                        write!(
-                            decoder,
-                            "  | Undefined _ -> failwith \"Undefined discriminant\"\n"
-                        )
-                        .unwrap();
-                    } else {
-                        print!("  {{");
-                        write!(decoder, "  {{").unwrap();
-                        let mut is_first_field = true;
-                        let fields = struct_node.get_fields().unwrap();
-                        for field in fields.iter() {
-                            let name = field.get_name().unwrap().to_str().unwrap();
-                            match field.which().unwrap() {
-                                schema_capnp::field::Slot(slot) => {
-                                    if is_first_field {
-                                        is_first_field = false;
-                                    } else {
-                                        print!(";");
-                                        write!(decoder, ";").unwrap();
-                                    }
-                                    let snake_name = pascal_to_snake(name);
-                                    let escaped_snake_name = escape_keyword(snake_name.clone());
-                                    print!("\n    {}: ", escaped_snake_name);
-                                    write!(decoder, "\n    {} = ", escaped_snake_name).unwrap();
-                                    let type_ = slot.get_type().unwrap();
-                                    print_type(&node_name_map, Some(&param_env), type_);
-                                    print_type_decoding(
-                                        decoder,
-                                        &node_name_map,
-                                        Some(&param_env),
-                                        type_,
-                                        &format!("({}.{}_get r)", nested_reader_path, snake_name),
-                                    );
-                                }
-                                _ => todo!(),
-                            }
-                        }
-                        print!("\n  }}");
-                        write!(decoder, "\n  }}").unwrap();
-                    }
-                }
+        let nested_node = get_node(node_map, nested_id)?;
+        if has_annotation(nested_node.get_annotations()?, SKIP_ANNOTATION_ID)? {
+            continue;
+        }
+        if let schema_capnp::node::Interface(_) = nested_node.which()? {
+            print_doc_comment(&mut out.types, node_doc(out.docs, nested_id)?);
+            let name = get_name(node_name_map, nested_id)?;
+            let escaped_name = escape_keyword(name.to_string());
+            writeln!(
+                out.types,
+                "type {} = {}.t S.MessageWrapper.Capability.t",
+                escaped_name, nested_reader_path
+            )
+            .unwrap();
+            // Capabilities are opaque to the derived functions.
+            if out.options.derives("show") {
+                writeln!(
+                    out.deriving_helpers,
+                    "let pp_{} fmt (_: {}) = Format.pp_print_string fmt \"<capability>\" [@@ocaml.warning \"-32\"]",
+                    name, escaped_name
+                )
+                .unwrap();
             }
-            schema_capnp::node::Which::Enum(enum_) => {
-                if *is_first_type {
-                    *is_first_type = false;
-                    print!("type ");
-                    write!(decoder, "let rec ").unwrap();
-                } else {
-                    print!("\nand ");
-                    write!(decoder, "\nand ").unwrap();
-                }
-                let name = node_name_map.get(&nested_id).unwrap();
-                print!("{} =", name);
-                write!(
-                    decoder,
-                    "decode_{} (r: {}.t): {} = match r with",
-                    name, nested_reader_path, name
+            if out.options.derives("eq") {
+                writeln!(
+                    out.deriving_helpers,
+                    "let equal_{} (_: {}) (_: {}) = true [@@ocaml.warning \"-32\"]",
+                    name, escaped_name, escaped_name
                 )
                 .unwrap();
-                let enumerants = enum_.get_enumerants().unwrap();
-                for enumerant in enumerants.iter() {
-                    let name = enumerant.get_name().unwrap().to_str().unwrap();
-                    let capitalized_name = name
-                        .chars()
-                        .next()
-                        .unwrap()
-                        .to_uppercase()
-                        .collect::<String>()
-                        + &name[1..];
-                    print!("\n  | {}", capitalized_name);
-                    write!(
-                        decoder,
-                        "\n  | {} -> {}",
-                        capitalized_name, capitalized_name
-                    )
-                    .unwrap();
-                }
-                write!(
-                    decoder,
-                    "\n  | Undefined _ -> failwith \"Undefined enumerant\""
+            }
+            if out.options.derives("ord") {
+                writeln!(
+                    out.deriving_helpers,
+                    "let compare_{} (_: {}) (_: {}) = 0 [@@ocaml.warning \"-32\"]",
+                    name, escaped_name, escaped_name
                 )
                 .unwrap();
             }
-            _ => todo!(),
         }
+        print_capability_types(
+            out,
+            node_map,
+            node_name_map,
+            &nested_reader_path,
+            nested_node.get_nested_nodes()?,
+        )?;
     }
+    Ok(())
 }
 
-fn process_requested_file(
+/// The type of the errors returned by decoders in result mode: the field names
+/// and list indices leading from the decoded value to the offending one, and
+/// what is wrong with it. It is structural so that the errors of decoders
+/// generated for different schema files are compatible.
+const DECODE_ERROR_TYPE: &str = include_str!("ocaml/decode_error_type.ml");
+
+/// The helper functions used by decoders in result mode.
+const RESULT_HELPERS: &str = include_str!("ocaml/result_helpers.ml");
+
+/// The function validating `Text` values with `--validate-utf8`, which fails
+/// on strings that are not UTF-8.
+const UTF8_HELPERS: &str = include_str!("ocaml/utf8_helpers.ml");
+
+/// Like `UTF8_HELPERS`, in result mode.
+const UTF8_RESULT_HELPERS: &str = include_str!("ocaml/utf8_result_helpers.ml");
+
+/// Shadows `Stdint` with a module that adds the functions the show, eq and ord
+/// derivers expect for its types.
+const STDINT_DERIVING_HELPERS: &str = include_str!("ocaml/stdint_deriving_helpers.ml");
+
+/// Shadows `Seq` with a module that adds the printer the show deriver expects
+/// for its type, the equality and comparison being in the standard library.
+const SEQ_DERIVING_HELPERS: &str = include_str!("ocaml/seq_deriving_helpers.ml");
+
+/// The functions converting integers between the representations capnp-ocaml
+/// uses and the ones chosen by `$OCaml.repr` and the command line options,
+/// which raise `Invalid_argument` for integers out of range, and a function
+/// making such a conversion return a decoding result instead.
+const INT_HELPERS: &str = include_str!("ocaml/int_helpers.ml");
+
+/// Returns the implementation and interface of the decoder module for
+/// `requested_file`.
+fn generate_file(
     node_map: &HashMap<u64, schema_capnp::node::Reader<'_>>,
+    docs: &HashMap<u64, schema_capnp::node::source_info::Reader<'_>>,
     requested_file: requested_file::Reader,
-) {
-    let mut decoder = String::new();
+    mut options: Options,
+) -> Result<(String, String)> {
     // Create a map of node id to node name
     let mut node_name_map = std::collections::HashMap::new();
     let id = requested_file.get_id();
-    let node = node_map.get(&id).unwrap();
-    assert!(match node.which().unwrap() {
-        schema_capnp::node::File(()) => true,
-        _ => false,
-    });
-    let nested_nodes = node.get_nested_nodes().unwrap();
-    enter_nested_nodes(&node_map, &mut node_name_map, "", nested_nodes);
-
-    let mut is_first_type = true;
-    let id = requested_file.get_id();
-    let filename = requested_file.get_filename().unwrap().to_str().unwrap();
-    let basename = std::path::Path::new(filename)
-        .file_stem()
-        .unwrap()
-        .to_str()
-        .unwrap();
-    let capitalized_basename = basename
-        .chars()
-        .next()
-        .unwrap()
-        .to_uppercase()
-        .collect::<String>()
-        + &basename[1..];
-    let node = node_map.get(&id).unwrap();
-    assert!(match node.which().unwrap() {
-        schema_capnp::node::File(()) => true,
-        _ => false,
-    });
-    let nested_nodes = node.get_nested_nodes().unwrap();
+    let node = get_node(node_map, id)?;
+    if !matches!(node.which()?, schema_capnp::node::File(())) {
+        return Err(Error::new("requested node is not a file"));
+    }
+    // `capnp compile -o` passes no arguments to plugins, so the schema file can
+    // give options too, which override the command line's.
+    if let Some(value) = find_annotation(node.get_annotations()?, OPTIONS_ANNOTATION_ID)? {
+        let schema_capnp::value::List(list) = value.which()? else {
+            return Err(Error::new(format!(
+                "annotation @0x{:x} must be a List(Text)",
+                OPTIONS_ANNOTATION_ID
+            )));
+        };
+        let args: capnp::text_list::Reader = list.get_as()?;
+        for arg in args.iter() {
+            options.set(arg?.to_str()?)?;
+        }
+    }
+    let nested_nodes = node.get_nested_nodes()?;
+    enter_nested_nodes(node_map, &mut node_name_map, "", nested_nodes)?;
+    let imported_modules = enter_imports(node_map, &mut node_name_map, requested_file)?;
+
+    let mut out = Output {
+        options,
+        docs,
+        types: String::new(),
+        decoder: String::new(),
+        encoder: String::new(),
+        interface: String::new(),
+        defaults: String::new(),
+        constants: String::new(),
+        deriving_helpers: String::new(),
+        converters: String::new(),
+        pointer_helpers: String::new(),
+        doc: String::new(),
+        definitions: Vec::new(),
+    };
+    let mut pointer_kinds = BTreeSet::new();
+    collect_nested_pointer_kinds(node_map, nested_nodes, &mut pointer_kinds)?;
+    print_pointer_types(&mut out, &pointer_kinds);
+    print_capability_types(&mut out, node_map, &node_name_map, "R", nested_nodes)?;
+    let capability_types = std::mem::take(&mut out.types);
     print_nested_nodes(
-        &mut decoder,
-        &node_map,
+        &mut out,
+        node_map,
         &node_name_map,
-        &mut is_first_type,
+        None,
         "R",
+        "B",
         nested_nodes,
-    );
-    println!();
-    println!();
-    println!(
+    )?;
+    print_definition_groups(&mut out);
+    // A blank line separates the capability types from the other types.
+    let blank_line_after = |code: &str| {
+        if code.is_empty() || out.types.is_empty() {
+            ""
+        } else {
+            "\n"
+        }
+    };
+
+    let filename = requested_file.get_filename()?.to_str()?;
+    let module_name = module_name(filename)?;
+    let mut implementation = String::new();
+    writeln!(
+        implementation,
         "module S = {}.Make (Capnp.BytesMessage)",
-        capitalized_basename
-    );
-    println!("module R = S.Reader");
-    println!();
-    println!("{}", decoder);
+        module_name
+    )
+    .unwrap();
+    writeln!(implementation, "module R = S.Reader").unwrap();
+    writeln!(implementation, "module B = S.Builder").unwrap();
+    for module in imported_modules {
+        writeln!(
+            implementation,
+            "module {0} = {0}.Make (Capnp.BytesMessage)",
+            module
+        )
+        .unwrap();
+    }
+    writeln!(implementation).unwrap();
+    if out.options.result {
+        writeln!(implementation, "{}", DECODE_ERROR_TYPE).unwrap();
+        writeln!(implementation, "{}", RESULT_HELPERS).unwrap();
+    }
+    if ["show", "eq", "ord"]
+        .iter()
+        .any(|deriver| out.options.derives(deriver))
+    {
+        writeln!(implementation, "{}", STDINT_DERIVING_HELPERS).unwrap();
+        let is_seq = |field: schema_capnp::field::Reader, _: schema_capnp::type_::Reader| {
+            Ok(text_annotation(field.get_annotations()?, REPR_ANNOTATION_ID)? == Some("seq"))
+        };
+        if out.options.list_repr == ListRepr::Seq
+            || nested_have_slot(node_map, nested_nodes, &is_seq)?
+        {
+            writeln!(implementation, "{}", SEQ_DERIVING_HELPERS).unwrap();
+        }
+    }
+    // Fields of integer types with a `$OCaml.repr` need the conversions too.
+    let has_int_repr = |field: schema_capnp::field::Reader, type_: schema_capnp::type_::Reader| {
+        Ok(IntRepr::of_type(type_)?.is_some()
+            && has_annotation(field.get_annotations()?, REPR_ANNOTATION_ID)?)
+    };
+    if !out.options.int_reprs.is_empty() || nested_have_slot(node_map, nested_nodes, &has_int_repr)?
+    {
+        writeln!(implementation, "{}", INT_HELPERS).unwrap();
+    }
+    if out.options.validate_utf8 {
+        if out.options.result {
+            writeln!(implementation, "{}", UTF8_RESULT_HELPERS).unwrap();
+        } else {
+            writeln!(implementation, "{}", UTF8_HELPERS).unwrap();
+        }
+    }
+    for format in &out.options.formats {
+        writeln!(implementation, "{}", format.helpers()).unwrap();
+    }
+    if out.options.data_repr == DataRepr::Private {
+        writeln!(implementation, "{}", data_type(&out.options, false)).unwrap();
+    }
+    writeln!(
+        implementation,
+        "{}{}{}{}",
+        capability_types,
+        out.deriving_helpers,
+        blank_line_after(&format!("{}{}", capability_types, out.deriving_helpers)),
+        out.types
+    )
+    .unwrap();
+    writeln!(implementation).unwrap();
+    writeln!(implementation, "{}", out.decoder).unwrap();
+    writeln!(implementation).unwrap();
+    if !out.pointer_helpers.is_empty() {
+        writeln!(implementation, "{}", out.pointer_helpers).unwrap();
+    }
+    writeln!(implementation, "{}", out.encoder).unwrap();
+    if !out.converters.is_empty() {
+        writeln!(implementation).unwrap();
+        writeln!(implementation, "{}", out.converters).unwrap();
+    }
+    // Default values and constants of struct types are decoded by the decoders
+    // above.
+    if !out.defaults.is_empty() || !out.constants.is_empty() {
+        writeln!(implementation).unwrap();
+        if !out.defaults.is_empty() {
+            write!(implementation, "{}", EMPTY_MESSAGE).unwrap();
+        }
+        write!(implementation, "{}{}", out.defaults, out.constants).unwrap();
+    }
+
+    // The interface refers to the capnp-ocaml modules through local
+    // substitutions, so that the type definitions read the same in both files.
+    let mut interface = String::new();
+    writeln!(
+        interface,
+        "module S := {}.Make (Capnp.BytesMessage)",
+        module_name
+    )
+    .unwrap();
+    writeln!(interface, "module R := S.Reader").unwrap();
+    writeln!(interface, "module B := S.Builder").unwrap();
+    writeln!(interface).unwrap();
+    if out.options.result {
+        writeln!(interface, "{}", DECODE_ERROR_TYPE).unwrap();
+    }
+    if out.options.data_repr == DataRepr::Private {
+        writeln!(interface, "{}", data_type(&out.options, true)).unwrap();
+    }
+    writeln!(
+        interface,
+        "{}{}{}",
+        capability_types,
+        blank_line_after(&capability_types),
+        out.types
+    )
+    .unwrap();
+    writeln!(interface).unwrap();
+    write!(interface, "{}", out.interface).unwrap();
+    Ok((implementation, interface))
 }
 
-fn main() {
+/// Writes the implementation and interface of the decoder module for
+/// `requested_file` to `<basename>_decoder.ml` and `<basename>_decoder.mli`,
+/// next to the path of the schema file relative to the output directory.
+fn process_requested_file(
+    node_map: &HashMap<u64, schema_capnp::node::Reader<'_>>,
+    docs: &HashMap<u64, schema_capnp::node::source_info::Reader<'_>>,
+    requested_file: requested_file::Reader,
+    options: Options,
+) -> Result<()> {
+    let (implementation, interface) = generate_file(node_map, docs, requested_file, options)?;
+    let filename = requested_file.get_filename()?.to_str()?;
+    let directory = std::path::Path::new(filename)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+    let stem = file_stem(filename);
+    write_file(
+        &directory.join(format!("{}_decoder.ml", stem)),
+        &implementation,
+    )?;
+    write_file(&directory.join(format!("{}_decoder.mli", stem)), &interface)?;
+    Ok(())
+}
+
+/// Writes `contents` to file `path`, creating its directory if necessary.
+fn write_file(path: &std::path::Path, contents: &str) -> Result<()> {
+    let result = match path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => std::fs::create_dir_all(directory),
+        _ => Ok(()),
+    }
+    .and_then(|()| std::fs::write(path, contents));
+    result.map_err(|error| Error::new(format!("cannot write {}: {}", path.display(), error)))
+}
+
+fn run() -> Result<()> {
     let stdin = ::std::io::stdin();
     let message_reader =
-        capnp::serialize::read_message(&mut stdin.lock(), ::capnp::message::ReaderOptions::new())
-            .unwrap();
-    let code_generator_request = message_reader
-        .get_root::<schema_capnp::code_generator_request::Reader>()
-        .unwrap();
-    let nodes = code_generator_request.get_nodes().unwrap();
+        capnp::serialize::read_message(&mut stdin.lock(), ::capnp::message::ReaderOptions::new())?;
+    let code_generator_request =
+        message_reader.get_root::<schema_capnp::code_generator_request::Reader>()?;
+    let nodes = code_generator_request.get_nodes()?;
     // Create a map of node id to node
     let mut node_map = std::collections::HashMap::new();
     for node in nodes.iter() {
         let id = node.get_id();
         node_map.insert(id, node);
     }
-    let requested_files = code_generator_request.get_requested_files().unwrap();
+    let mut docs = std::collections::HashMap::new();
+    for source_info in code_generator_request.get_source_info()?.iter() {
+        docs.insert(source_info.get_id(), source_info);
+    }
+    let options = Options::parse(std::env::args().skip(1))?;
+    let requested_files = code_generator_request.get_requested_files()?;
     for requested_file in requested_files.iter() {
-        process_requested_file(&node_map, requested_file);
+        let file_node = get_node(&node_map, requested_file.get_id())?;
+        process_requested_file(&node_map, &docs, requested_file, options.clone())
+            .map_err(|error| error.in_node(file_node))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(error) = run() {
+        error.report();
+        std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests;
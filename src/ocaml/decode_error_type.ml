@@ -0,0 +1,6 @@
+type decode_error = string list * [
+  | `Undefined_discriminant of string * int
+  | `Undefined_enumerant of string * int
+  | `Out_of_range of string
+  | `Invalid_utf8
+]
@@ -0,0 +1,29 @@
+module Int_repr = struct
+  let fail type_name value = invalid_arg (value ^ " is out of the range of " ^ type_name)
+  let checked f n = match f n with x -> Ok x | exception Invalid_argument e -> Error ([], `Out_of_range e)
+  let int_of_int64 n =
+    if Int64.compare n (Int64.of_int min_int) < 0 || Int64.compare n (Int64.of_int max_int) > 0
+    then fail "int" (Int64.to_string n)
+    else Int64.to_int n
+  let int_of_uint64 n =
+    if Stdint.Uint64.compare n (Stdint.Uint64.of_int max_int) > 0
+    then fail "int" (Stdint.Uint64.to_string n)
+    else Stdint.Uint64.to_int n
+  let int64_of_uint64 n =
+    if Stdint.Uint64.compare n (Stdint.Uint64.of_int64 Int64.max_int) > 0
+    then fail "int64" (Stdint.Uint64.to_string n)
+    else Stdint.Uint64.to_int64 n
+  let int32_of_int n =
+    if n < Int32.to_int Int32.min_int || n > Int32.to_int Int32.max_int
+    then fail "Int32" (string_of_int n)
+    else Int32.of_int n
+  let uint32_of_int n =
+    if n < 0 || n > 0xffff_ffff then fail "UInt32" (string_of_int n) else Stdint.Uint32.of_int n
+  let uint32_of_int64 n =
+    if Int64.compare n 0L < 0 || Int64.compare n 0xffff_ffffL > 0
+    then fail "UInt32" (Int64.to_string n)
+    else Stdint.Uint32.of_int64 n
+  let uint64_of_int n = if n < 0 then fail "UInt64" (string_of_int n) else Stdint.Uint64.of_int n
+  let uint64_of_int64 n =
+    if Int64.compare n 0L < 0 then fail "UInt64" (Int64.to_string n) else Stdint.Uint64.of_int64 n
+end [@@ocaml.warning "-32"]
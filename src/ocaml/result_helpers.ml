@@ -0,0 +1,13 @@
+let ( let* ) = Result.bind [@@ocaml.warning "-32"]
+
+let in_field name = Result.map_error (fun (path, e) -> (name :: path, e)) [@@ocaml.warning "-32"]
+
+let decode_list f a =
+  let rec go i acc = function
+    | [] -> Ok (List.rev acc)
+    | x :: xs ->
+      match f x with
+      | Ok y -> go (i + 1) (y :: acc) xs
+      | Error (path, e) -> Error (string_of_int i :: path, e)
+  in
+  go 0 [] (Capnp.Array.to_list a) [@@ocaml.warning "-32"]
@@ -0,0 +1,6 @@
+module Seq = struct
+  include Seq
+  let pp pp_x fmt xs =
+    let pp_sep fmt () = Format.fprintf fmt ";@ " in
+    Format.fprintf fmt "@[<2>[%a]@]" (Format.pp_print_seq ~pp_sep pp_x) xs
+end [@@ocaml.warning "-32"]
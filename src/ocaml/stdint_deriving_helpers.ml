@@ -0,0 +1,9 @@
+module Stdint = struct
+  include Stdint
+  let pp_uint32 fmt x = Format.pp_print_string fmt (Uint32.to_string x)
+  let pp_uint64 fmt x = Format.pp_print_string fmt (Uint64.to_string x)
+  let equal_uint32 x y = Uint32.compare x y = 0
+  let equal_uint64 x y = Uint64.compare x y = 0
+  let compare_uint32 = Uint32.compare
+  let compare_uint64 = Uint64.compare
+end [@@ocaml.warning "-32"]
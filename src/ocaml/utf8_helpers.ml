@@ -0,0 +1 @@
+let validate_utf_8 s = if String.is_valid_utf_8 s then s else failwith "Invalid UTF-8" [@@ocaml.warning "-32"]
@@ -0,0 +1 @@
+let validate_utf_8 s = if String.is_valid_utf_8 s then Ok s else Error ([], `Invalid_utf8) [@@ocaml.warning "-32"]
@@ -0,0 +1,1220 @@
+use super::literal::float_literal;
+use super::*;
+
+fn error_message<T>(result: Result<T>) -> String {
+    match result {
+        Ok(_) => panic!("expected an error"),
+        Err(error) => error.message,
+    }
+}
+
+fn definition(node_id: u64, dependencies: &[u64]) -> Definition {
+    Definition {
+        node_id,
+        dependencies: dependencies.to_vec(),
+        has_union_type: false,
+        doc: String::new(),
+        types: String::new(),
+        decoder: String::new(),
+        encoder: String::new(),
+        converters: String::new(),
+    }
+}
+
+#[test]
+fn recursive_groups_come_after_their_dependencies() {
+    let definitions = [
+        definition(1, &[2]),
+        definition(2, &[3, 99]),
+        definition(3, &[2, 3]),
+        definition(4, &[]),
+    ];
+    assert_eq!(
+        recursive_groups(&definitions),
+        vec![vec![1, 2], vec![0], vec![3]]
+    );
+}
+
+#[test]
+fn recursive_groups_of_independent_definitions_keep_their_order() {
+    let definitions = [definition(1, &[]), definition(2, &[1]), definition(3, &[])];
+    assert_eq!(
+        recursive_groups(&definitions),
+        vec![vec![0], vec![1], vec![2]]
+    );
+}
+
+#[test]
+fn int_repr_parse() {
+    assert!(IntRepr::Int32.parse("int32").ok() == Some(IntRepr::Int32));
+    assert!(IntRepr::Int32.parse("int").ok() == Some(IntRepr::Int));
+    assert!(IntRepr::Uint32.parse("int64").ok() == Some(IntRepr::Int64));
+    assert!(IntRepr::Uint64.parse("uint64").ok() == Some(IntRepr::Uint64));
+    assert_eq!(
+        error_message(IntRepr::Int32.parse("int64")),
+        "unknown representation \"int64\" of int32 integers; expected \"int32\" or \"int\""
+    );
+    assert_eq!(
+        error_message(IntRepr::Uint64.parse("uint32")),
+        "unknown representation \"uint32\" of uint64 integers; \
+         expected \"uint64\", \"int\" or \"int64\""
+    );
+}
+
+#[test]
+fn int_repr_literal() {
+    assert_eq!(IntRepr::Int.literal(-7).ok().unwrap(), "-7");
+    assert_eq!(IntRepr::Int32.literal(-7).ok().unwrap(), "-7l");
+    assert_eq!(
+        IntRepr::Int64.literal(i64::MIN.into()).ok().unwrap(),
+        "-9223372036854775808L"
+    );
+    assert_eq!(
+        IntRepr::Uint32.literal(u32::MAX.into()).ok().unwrap(),
+        "Stdint.Uint32.of_string \"4294967295\""
+    );
+    assert_eq!(
+        IntRepr::Uint64.literal(u64::MAX.into()).ok().unwrap(),
+        "Stdint.Uint64.of_string \"18446744073709551615\""
+    );
+    assert_eq!(
+        error_message(IntRepr::Int.literal(1 << 62)),
+        "4611686018427387904 is out of the range of int"
+    );
+    assert_eq!(
+        error_message(IntRepr::Int32.literal(i128::from(i32::MAX) + 1)),
+        "2147483648 is out of the range of int32"
+    );
+    assert_eq!(
+        error_message(IntRepr::Uint64.literal(-1)),
+        "-1 is out of the range of Stdint.uint64"
+    );
+}
+
+fn parse_options(args: &[&str]) -> Result<Options> {
+    Options::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn options_parse() {
+    let options = parse_options(&[]).ok().unwrap();
+    assert!(!options.result);
+    assert!(!options.unknown);
+    assert!(!options.optional_pointers);
+    assert!(options.deriving.is_empty());
+    assert!(options.formats.is_empty());
+    let options = parse_options(&["--result", "--unknown", "--optional-pointers"])
+        .ok()
+        .unwrap();
+    assert!(options.result);
+    assert!(options.unknown);
+    assert!(options.optional_pointers);
+}
+
+#[test]
+fn options_parse_formats() {
+    let options = parse_options(&["--sexp", "--json", "--sexp"]).ok().unwrap();
+    assert!(options.formats == [Format::Sexp, Format::Json]);
+}
+
+#[test]
+fn options_parse_deriving() {
+    let options = parse_options(&["--deriving=show, eq { strict }"])
+        .ok()
+        .unwrap();
+    assert_eq!(options.deriving, ["show", "eq { strict }"]);
+    assert!(options.derives("eq"));
+    assert!(!options.derives("ord"));
+    let options = parse_options(&["--deriving=show", "--deriving=eq"])
+        .ok()
+        .unwrap();
+    assert_eq!(options.deriving, ["eq"]);
+}
+
+#[test]
+fn options_parse_int_reprs() {
+    let options = parse_options(&["--int64=int", "--uint32=int64", "--int64=int64"])
+        .ok()
+        .unwrap();
+    assert!(options.int_repr(IntRepr::Int64) == IntRepr::Int64);
+    assert!(options.int_repr(IntRepr::Uint32) == IntRepr::Int64);
+    assert!(options.int_repr(IntRepr::Int32) == IntRepr::Int32);
+}
+
+#[test]
+fn options_parse_data() {
+    let options = parse_options(&[]).ok().unwrap();
+    assert!(options.data_repr == DataRepr::String);
+    assert!(!options.validate_utf8);
+    let options = parse_options(&["--data=bytes", "--validate-utf8"])
+        .ok()
+        .unwrap();
+    assert!(options.data_repr == DataRepr::Bytes);
+    assert!(options.validate_utf8);
+}
+
+#[test]
+fn options_parse_lists() {
+    let options = parse_options(&[]).ok().unwrap();
+    assert!(options.list_repr == ListRepr::List);
+    let options = parse_options(&["--lists=seq"]).ok().unwrap();
+    assert!(options.list_repr == ListRepr::Seq);
+    let options = parse_options(&["--lists=seq", "--lists=array"])
+        .ok()
+        .unwrap();
+    assert!(options.list_repr == ListRepr::Array);
+}
+
+#[test]
+fn options_parse_rejects_invalid_values() {
+    assert_eq!(
+        error_message(parse_options(&["--deriving=show,,eq"])),
+        "invalid list of derivers in --deriving=show,,eq"
+    );
+    assert_eq!(
+        error_message(parse_options(&["--lists=vector"])),
+        "unknown list representation \"vector\"; expected \"list\", \"array\" or \"seq\""
+    );
+    assert_eq!(
+        error_message(parse_options(&["--data=blob"])),
+        "unknown data representation \"blob\"; expected \"string\", \"bytes\" or \"private\""
+    );
+    assert_eq!(
+        error_message(parse_options(&["--int32=uint32"])),
+        "unknown representation \"uint32\" of int32 integers; expected \"int32\" or \"int\""
+    );
+    assert_eq!(
+        error_message(parse_options(&["--result", "--frobnicate"])),
+        "unknown option --frobnicate"
+    );
+    assert_eq!(
+        error_message(parse_options(&["--result=yes"])),
+        "unknown option --result=yes"
+    );
+}
+
+#[test]
+fn string_literal_escapes() {
+    assert_eq!(string_literal(b""), "\"\"");
+    assert_eq!(
+        string_literal(b"say \"hi\"\\\n"),
+        "\"say \\\"hi\\\"\\\\\\n\""
+    );
+    assert_eq!(
+        string_literal(b"\x00\t\x7f\xff"),
+        "\"\\x00\\x09\\x7f\\xff\""
+    );
+    assert_eq!(string_literal("é".as_bytes()), "\"\\xc3\\xa9\"");
+}
+
+#[test]
+fn float_literal_special_values() {
+    assert_eq!(float_literal(f64::NAN), "Float.nan");
+    assert_eq!(float_literal(f64::INFINITY), "Float.infinity");
+    assert_eq!(float_literal(f64::NEG_INFINITY), "Float.neg_infinity");
+    assert_eq!(float_literal(1.0), "1.0");
+    assert_eq!(float_literal(-0.5), "-0.5");
+    assert_eq!(float_literal(1e300), "1e300");
+}
+
+#[test]
+fn doc_comment_breaks_up_delimiters() {
+    assert_eq!(doc_comment("  \n ", ""), "");
+    assert_eq!(
+        doc_comment("Calls f (*x*) \"*)\".\n", ""),
+        "(** Calls f ( *x* ) \"* )\". *)"
+    );
+    assert_eq!(
+        doc_comment("An inch is 2.54\" long.", ""),
+        "(** An inch is 2.54'' long. *)"
+    );
+}
+
+#[test]
+fn doc_comment_indents_continuation_lines() {
+    assert_eq!(
+        doc_comment("First line.   \n\nThird line.\n  Indented.", "  "),
+        "(** First line.\n\n      Third line.\n        Indented. *)"
+    );
+}
+
+/// A type in a hand-built schema.
+enum Type {
+    Void,
+    Int32,
+    Int64,
+    Uint32,
+    Float64,
+    Text,
+    Data,
+    List(Box<Type>),
+    Enum(u64),
+    Struct(u64),
+    /// Struct `id`, with the types bound to the parameters of its enclosing
+    /// generic struct `scope_id`.
+    Branded(u64, u64, Vec<Type>),
+    /// Parameter `index` of the generic struct `scope_id`.
+    Param(u64, u16),
+    AnyPointer,
+    AnyStruct,
+    AnyList,
+    Capability,
+}
+
+enum NodeKind {
+    File,
+    Struct { fields: Vec<Field>, is_group: bool },
+    Enum(Vec<&'static str>),
+}
+
+/// A node in a hand-built schema, nested in node `scope_id`.
+struct Node {
+    id: u64,
+    scope_id: u64,
+    name: &'static str,
+    kind: NodeKind,
+    annotations: Vec<Annotation>,
+    doc: &'static str,
+    parameters: Vec<&'static str>,
+}
+
+struct Field {
+    name: &'static str,
+    discriminant: u16,
+    kind: FieldKind,
+    annotations: Vec<Annotation>,
+    doc: &'static str,
+}
+
+struct Annotation {
+    id: u64,
+    value: Value,
+}
+
+/// The value of an annotation.
+enum Value {
+    Void,
+    Text(&'static str),
+    TextList(Vec<&'static str>),
+}
+
+impl Node {
+    fn annotated(mut self, id: u64, value: Value) -> Node {
+        self.annotations.push(Annotation { id, value });
+        self
+    }
+
+    fn documented(mut self, doc: &'static str) -> Node {
+        self.doc = doc;
+        self
+    }
+
+    fn generic(mut self, parameters: &[&'static str]) -> Node {
+        self.parameters = parameters.to_vec();
+        self
+    }
+}
+
+impl Field {
+    fn annotated(mut self, id: u64, value: Value) -> Field {
+        self.annotations.push(Annotation { id, value });
+        self
+    }
+
+    fn documented(mut self, doc: &'static str) -> Field {
+        self.doc = doc;
+        self
+    }
+}
+
+enum FieldKind {
+    Slot(Type),
+    /// A group, with the id of its node.
+    Group(u64),
+}
+
+fn file(id: u64, filename: &'static str) -> Node {
+    Node {
+        id,
+        scope_id: 0,
+        name: filename,
+        kind: NodeKind::File,
+        annotations: Vec::new(),
+        doc: "",
+        parameters: Vec::new(),
+    }
+}
+
+fn struct_(id: u64, scope_id: u64, name: &'static str, fields: Vec<Field>) -> Node {
+    Node {
+        id,
+        scope_id,
+        name,
+        kind: NodeKind::Struct {
+            fields,
+            is_group: false,
+        },
+        annotations: Vec::new(),
+        doc: "",
+        parameters: Vec::new(),
+    }
+}
+
+fn group(id: u64, scope_id: u64, name: &'static str, fields: Vec<Field>) -> Node {
+    Node {
+        id,
+        scope_id,
+        name,
+        kind: NodeKind::Struct {
+            fields,
+            is_group: true,
+        },
+        annotations: Vec::new(),
+        doc: "",
+        parameters: Vec::new(),
+    }
+}
+
+fn enum_(id: u64, scope_id: u64, name: &'static str, enumerants: &[&'static str]) -> Node {
+    Node {
+        id,
+        scope_id,
+        name,
+        kind: NodeKind::Enum(enumerants.to_vec()),
+        annotations: Vec::new(),
+        doc: "",
+        parameters: Vec::new(),
+    }
+}
+
+fn field(name: &'static str, type_: Type) -> Field {
+    Field {
+        name,
+        discriminant: NO_DISCRIMINANT,
+        kind: FieldKind::Slot(type_),
+        annotations: Vec::new(),
+        doc: "",
+    }
+}
+
+fn member(name: &'static str, discriminant: u16, type_: Type) -> Field {
+    Field {
+        name,
+        discriminant,
+        kind: FieldKind::Slot(type_),
+        annotations: Vec::new(),
+        doc: "",
+    }
+}
+
+fn group_member(name: &'static str, discriminant: u16, group_id: u64) -> Field {
+    Field {
+        name,
+        discriminant,
+        kind: FieldKind::Group(group_id),
+        annotations: Vec::new(),
+        doc: "",
+    }
+}
+
+fn list(element_type: Type) -> Type {
+    Type::List(Box::new(element_type))
+}
+
+fn build_type(mut builder: schema_capnp::type_::Builder, type_: &Type) {
+    match type_ {
+        Type::Void => builder.set_void(()),
+        Type::Int32 => builder.set_int32(()),
+        Type::Int64 => builder.set_int64(()),
+        Type::Uint32 => builder.set_uint32(()),
+        Type::Float64 => builder.set_float64(()),
+        Type::Text => builder.set_text(()),
+        Type::Data => builder.set_data(()),
+        Type::List(element_type) => {
+            build_type(builder.init_list().init_element_type(), element_type)
+        }
+        Type::Enum(id) => builder.init_enum().set_type_id(*id),
+        Type::Struct(id) => builder.init_struct().set_type_id(*id),
+        Type::Branded(id, scope_id, arguments) => {
+            let mut struct_ = builder.init_struct();
+            struct_.set_type_id(*id);
+            let mut scope = struct_.init_brand().init_scopes(1).get(0);
+            scope.set_scope_id(*scope_id);
+            let mut bindings = scope.init_bind(arguments.len() as u32);
+            for (i, argument) in arguments.iter().enumerate() {
+                build_type(bindings.reborrow().get(i as u32).init_type(), argument);
+            }
+        }
+        Type::Param(scope_id, index) => {
+            let mut parameter = builder.init_any_pointer().init_parameter();
+            parameter.set_scope_id(*scope_id);
+            parameter.set_parameter_index(*index);
+        }
+        Type::AnyPointer => builder
+            .init_any_pointer()
+            .init_unconstrained()
+            .set_any_kind(()),
+        Type::AnyStruct => builder
+            .init_any_pointer()
+            .init_unconstrained()
+            .set_struct(()),
+        Type::AnyList => builder.init_any_pointer().init_unconstrained().set_list(()),
+        Type::Capability => builder
+            .init_any_pointer()
+            .init_unconstrained()
+            .set_capability(()),
+    }
+}
+
+fn build_annotations(
+    mut builders: capnp::struct_list::Builder<schema_capnp::annotation::Owned>,
+    annotations: &[Annotation],
+) {
+    for (i, annotation) in annotations.iter().enumerate() {
+        let mut builder = builders.reborrow().get(i as u32);
+        builder.set_id(annotation.id);
+        match &annotation.value {
+            Value::Void => builder.init_value().set_void(()),
+            Value::Text(text) => builder.init_value().set_text(*text),
+            Value::TextList(texts) => {
+                let mut list = builder
+                    .init_value()
+                    .init_list()
+                    .initn_as::<capnp::text_list::Builder>(texts.len() as u32);
+                for (j, text) in texts.iter().enumerate() {
+                    list.set(j as u32, capnp::text::Reader::from(*text));
+                }
+            }
+        }
+    }
+}
+
+/// Returns a code generator request for the file that is the first of
+/// `nodes`, serialized.
+fn build_request(nodes: &[Node]) -> Vec<u8> {
+    let display_name = |node: &Node| {
+        let mut names = vec![node.name];
+        let mut scope_id = node.scope_id;
+        while scope_id != 0 {
+            let scope = nodes.iter().find(|scope| scope.id == scope_id).unwrap();
+            names.push(scope.name);
+            scope_id = scope.scope_id;
+        }
+        names.reverse();
+        match names.split_first() {
+            Some((filename, path)) if !path.is_empty() => {
+                format!("{}:{}", filename, path.join("."))
+            }
+            _ => node.name.to_string(),
+        }
+    };
+    let mut message = capnp::message::Builder::new_default();
+    let mut request = message.init_root::<schema_capnp::code_generator_request::Builder>();
+    let mut node_list = request.reborrow().init_nodes(nodes.len() as u32);
+    for (i, node) in nodes.iter().enumerate() {
+        let mut builder = node_list.reborrow().get(i as u32);
+        let display_name = display_name(node);
+        builder.set_id(node.id);
+        builder.set_display_name(&display_name[..]);
+        builder.set_display_name_prefix_length((display_name.len() - node.name.len()) as u32);
+        builder.set_scope_id(node.scope_id);
+        build_annotations(
+            builder
+                .reborrow()
+                .init_annotations(node.annotations.len() as u32),
+            &node.annotations,
+        );
+        if !node.parameters.is_empty() {
+            builder.set_is_generic(true);
+            let mut parameters = builder
+                .reborrow()
+                .init_parameters(node.parameters.len() as u32);
+            for (j, parameter) in node.parameters.iter().enumerate() {
+                parameters.reborrow().get(j as u32).set_name(*parameter);
+            }
+        }
+        let nested: Vec<&Node> = nodes
+            .iter()
+            .filter(|nested| {
+                nested.scope_id == node.id
+                    && !matches!(nested.kind, NodeKind::Struct { is_group: true, .. })
+            })
+            .collect();
+        let mut nested_nodes = builder.reborrow().init_nested_nodes(nested.len() as u32);
+        for (j, nested) in nested.iter().enumerate() {
+            let mut nested_node = nested_nodes.reborrow().get(j as u32);
+            nested_node.set_name(nested.name);
+            nested_node.set_id(nested.id);
+        }
+        match &node.kind {
+            NodeKind::File => builder.set_file(()),
+            NodeKind::Struct { fields, is_group } => {
+                let mut struct_ = builder.init_struct();
+                struct_.set_is_group(*is_group);
+                struct_.set_discriminant_count(
+                    fields
+                        .iter()
+                        .filter(|field| field.discriminant != NO_DISCRIMINANT)
+                        .count() as u16,
+                );
+                let mut field_list = struct_.init_fields(fields.len() as u32);
+                for (j, field) in fields.iter().enumerate() {
+                    let mut builder = field_list.reborrow().get(j as u32);
+                    builder.set_name(field.name);
+                    builder.set_code_order(j as u16);
+                    builder.set_discriminant_value(field.discriminant);
+                    build_annotations(
+                        builder
+                            .reborrow()
+                            .init_annotations(field.annotations.len() as u32),
+                        &field.annotations,
+                    );
+                    match &field.kind {
+                        FieldKind::Slot(type_) => {
+                            let mut slot = builder.init_slot();
+                            slot.set_offset(j as u32);
+                            build_type(slot.init_type(), type_);
+                        }
+                        FieldKind::Group(group_id) => builder.init_group().set_type_id(*group_id),
+                    }
+                }
+            }
+            NodeKind::Enum(enumerants) => {
+                let mut enumerant_list =
+                    builder.init_enum().init_enumerants(enumerants.len() as u32);
+                for (j, name) in enumerants.iter().enumerate() {
+                    let mut enumerant = enumerant_list.reborrow().get(j as u32);
+                    enumerant.set_name(*name);
+                    enumerant.set_code_order(j as u16);
+                }
+            }
+        }
+    }
+    let mut source_info_list = request.reborrow().init_source_info(nodes.len() as u32);
+    for (i, node) in nodes.iter().enumerate() {
+        let mut source_info = source_info_list.reborrow().get(i as u32);
+        source_info.set_id(node.id);
+        source_info.set_doc_comment(node.doc);
+        if let NodeKind::Struct { fields, .. } = &node.kind {
+            let mut members = source_info.init_members(fields.len() as u32);
+            for (j, field) in fields.iter().enumerate() {
+                members.reborrow().get(j as u32).set_doc_comment(field.doc);
+            }
+        }
+    }
+    let mut requested_file = request.init_requested_files(1).get(0);
+    requested_file.set_id(nodes[0].id);
+    requested_file.set_filename(nodes[0].name);
+    let mut bytes = Vec::new();
+    capnp::serialize::write_message(&mut bytes, &message).unwrap();
+    bytes
+}
+
+/// Runs the generator with command line arguments `args` on the file that is
+/// the first of `nodes`, returning the implementation and interface.
+fn generate(nodes: &[Node], args: &[&str]) -> Result<(String, String)> {
+    let bytes = build_request(nodes);
+    let message_reader =
+        capnp::serialize::read_message(&mut &bytes[..], capnp::message::ReaderOptions::new())?;
+    let request = message_reader.get_root::<schema_capnp::code_generator_request::Reader>()?;
+    let node_map = request
+        .get_nodes()?
+        .iter()
+        .map(|node| (node.get_id(), node))
+        .collect();
+    let requested_file = request.get_requested_files()?.get(0);
+    let docs = request
+        .get_source_info()?
+        .iter()
+        .map(|source_info| (source_info.get_id(), source_info))
+        .collect();
+    generate_file(&node_map, &docs, requested_file, parse_options(args)?)
+}
+
+/// Checks the code generated with `args` for `nodes` against
+/// `tests/golden/<name>.ml` and `.mli`, or updates them if `UPDATE_GOLDEN` is
+/// set.
+fn check_golden(name: &str, nodes: &[Node], args: &[&str]) {
+    let (implementation, interface) = match generate(nodes, args) {
+        Ok(code) => code,
+        Err(error) => panic!("cannot generate {}: {}", name, error.message),
+    };
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    for (extension, code) in [("ml", implementation), ("mli", interface)] {
+        let path = directory.join(format!("{}.{}", name, extension));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &code).unwrap();
+        } else {
+            let expected = std::fs::read_to_string(&path)
+                .unwrap_or_else(|error| panic!("cannot read {}: {}", path.display(), error));
+            assert!(
+                code == expected,
+                "{} differs from the generated code; rerun with UPDATE_GOLDEN=1 to update it",
+                path.display()
+            );
+        }
+    }
+}
+
+/// ```capnp
+/// struct Shape {
+///   # A shape on the canvas.
+///   # Shapes are drawn in order.
+///   name :Text;
+///   # The name shown in the legend.
+///   union {
+///     circle :group { radius :Float64; }
+///     # A circle around the origin.
+///     rectangle :group {
+///       width :Float64;
+///       height :Float64;
+///       union {
+///         sharp :Void;
+///         rounded :Float64;
+///       }
+///     }
+///     label :Text;
+///   }
+/// }
+/// ```
+fn shape_schema() -> Vec<Node> {
+    vec![
+        file(0xa000, "shapes.capnp"),
+        struct_(
+            0xa001,
+            0xa000,
+            "Shape",
+            vec![
+                field("name", Type::Text).documented("The name shown in the legend.\n"),
+                group_member("circle", 0, 0xa002).documented("A circle around the origin."),
+                group_member("rectangle", 1, 0xa003),
+                member("label", 2, Type::Text),
+            ],
+        )
+        .documented("A shape on the canvas.\nShapes are drawn in order."),
+        group(
+            0xa002,
+            0xa001,
+            "circle",
+            vec![field("radius", Type::Float64)],
+        ),
+        group(
+            0xa003,
+            0xa001,
+            "rectangle",
+            vec![
+                field("width", Type::Float64),
+                field("height", Type::Float64),
+                member("sharp", 0, Type::Void),
+                member("rounded", 1, Type::Float64),
+            ],
+        ),
+    ]
+}
+
+#[test]
+fn golden_union_groups() {
+    check_golden("shapes_decoder", &shape_schema(), &[]);
+}
+
+#[test]
+fn golden_json() {
+    check_golden("shapes_json_decoder", &shape_schema(), &["--json"]);
+}
+
+/// ```capnp
+/// enum Method { get @0; post @1; }
+/// struct Request { method :Method; headers :List(Header); }
+/// struct Header { name :Text; }
+/// ```
+fn request_schema() -> Vec<Node> {
+    vec![
+        file(0xb000, "request.capnp"),
+        enum_(0xb001, 0xb000, "Method", &["get", "post"]),
+        struct_(
+            0xb002,
+            0xb000,
+            "Request",
+            vec![
+                field("method", Type::Enum(0xb001)),
+                field("headers", list(Type::Struct(0xb003))),
+            ],
+        ),
+        struct_(0xb003, 0xb000, "Header", vec![field("name", Type::Text)]),
+    ]
+}
+
+#[test]
+fn golden_keywords() {
+    check_golden("request_decoder", &request_schema(), &[]);
+}
+
+#[test]
+fn golden_optional_pointers() {
+    check_golden(
+        "request_optional_decoder",
+        &request_schema(),
+        &["--optional-pointers"],
+    );
+}
+
+#[test]
+fn golden_sexp() {
+    check_golden("request_sexp_decoder", &request_schema(), &["--sexp"]);
+}
+
+#[test]
+fn golden_deriving() {
+    check_golden(
+        "request_deriving_decoder",
+        &request_schema(),
+        &["--deriving=show,eq"],
+    );
+}
+
+#[test]
+fn options_annotation_overrides_command_line() {
+    let mut nodes = request_schema();
+    nodes[0] = file(0xb000, "request.capnp").annotated(
+        OPTIONS_ANNOTATION_ID,
+        Value::TextList(vec!["--deriving=show,eq"]),
+    );
+    assert!(
+        generate(&nodes, &["--deriving=ord"]).ok().unwrap()
+            == generate(&request_schema(), &["--deriving=show,eq"])
+                .ok()
+                .unwrap()
+    );
+}
+
+#[test]
+fn golden_result_mode() {
+    check_golden("request_result_decoder", &request_schema(), &["--result"]);
+}
+
+/// ```capnp
+/// enum Color { red @0; green @1; }
+/// struct Paint {
+///   color :Color;
+///   shades :List(Color);
+///   union {
+///     matte :Void;
+///     gloss :Float64;
+///   }
+/// }
+/// ```
+fn paint_schema() -> Vec<Node> {
+    vec![
+        file(0xd000, "paint.capnp"),
+        enum_(0xd001, 0xd000, "Color", &["red", "green"]),
+        struct_(
+            0xd002,
+            0xd000,
+            "Paint",
+            vec![
+                field("color", Type::Enum(0xd001)),
+                field("shades", list(Type::Enum(0xd001))),
+                member("matte", 0, Type::Void),
+                member("gloss", 1, Type::Float64),
+            ],
+        ),
+    ]
+}
+
+#[test]
+fn golden_unknown() {
+    check_golden("paint_decoder", &paint_schema(), &["--unknown", "--json"]);
+}
+
+#[test]
+fn unknown_rejects_clashing_names() {
+    let nodes = [
+        file(0xd000, "paint.capnp"),
+        enum_(0xd001, 0xd000, "Color", &["red", "unknown"]),
+    ];
+    assert_eq!(
+        error_message(generate(&nodes, &["--unknown"])),
+        "this enumerant clashes with the `Unknown` constructor added by --unknown"
+    );
+}
+
+/// ```capnp
+/// using OCaml = import "ocaml_decoder.capnp";
+///
+/// struct Person $OCaml.name("contact") {
+///   name :Text $OCaml.name("full_name");
+///   tags :List(Text) $OCaml.repr("array");
+///   cache :Text $OCaml.skip;
+///   address :group $OCaml.inline {
+///     street :Text;
+///     city :Text;
+///   }
+/// }
+/// ```
+#[test]
+fn golden_annotations() {
+    let nodes = [
+        file(0xf000, "person.capnp"),
+        struct_(
+            0xf001,
+            0xf000,
+            "Person",
+            vec![
+                field("name", Type::Text).annotated(NAME_ANNOTATION_ID, Value::Text("full_name")),
+                field("tags", list(Type::Text)).annotated(REPR_ANNOTATION_ID, Value::Text("array")),
+                field("cache", Type::Text).annotated(SKIP_ANNOTATION_ID, Value::Void),
+                group_member("address", NO_DISCRIMINANT, 0xf002)
+                    .annotated(INLINE_ANNOTATION_ID, Value::Void),
+            ],
+        )
+        .annotated(NAME_ANNOTATION_ID, Value::Text("contact")),
+        group(
+            0xf002,
+            0xf001,
+            "address",
+            vec![field("street", Type::Text), field("city", Type::Text)],
+        ),
+    ];
+    check_golden("person_decoder", &nodes, &[]);
+}
+
+#[test]
+fn annotations_reject_skipped_union_members() {
+    let nodes = [
+        file(0xf000, "person.capnp"),
+        struct_(
+            0xf001,
+            0xf000,
+            "Person",
+            vec![
+                member("email", 0, Type::Text).annotated(SKIP_ANNOTATION_ID, Value::Void),
+                member("phone", 1, Type::Text),
+            ],
+        ),
+    ];
+    assert_eq!(
+        error_message(generate(&nodes, &[])),
+        "union members cannot be skipped"
+    );
+}
+
+#[test]
+fn annotations_reject_invalid_names() {
+    let person = |name| {
+        [
+            file(0xf000, "person.capnp"),
+            struct_(0xf001, 0xf000, "Person", vec![field("name", Type::Text)])
+                .annotated(NAME_ANNOTATION_ID, Value::Text(name)),
+        ]
+    };
+    assert_eq!(
+        error_message(generate(&person(""), &[])),
+        "$OCaml.name(\"\") is not a valid OCaml lowercase identifier"
+    );
+    assert_eq!(
+        error_message(generate(&person("type"), &[])),
+        "$OCaml.name(\"type\") is not a valid OCaml lowercase identifier"
+    );
+    let nodes = [
+        file(0xf000, "shape.capnp"),
+        struct_(
+            0xf001,
+            0xf000,
+            "Shape",
+            vec![
+                member("circle", 0, Type::Float64)
+                    .annotated(NAME_ANNOTATION_ID, Value::Text("round")),
+                member("square", 1, Type::Float64),
+            ],
+        ),
+    ];
+    assert_eq!(
+        error_message(generate(&nodes, &[])),
+        "$OCaml.name(\"round\") is not a valid OCaml constructor name"
+    );
+}
+
+#[test]
+fn capitalize_rejects_empty_names() {
+    assert_eq!(capitalize("élan").ok().unwrap(), "Élan");
+    assert_eq!(
+        error_message(capitalize("")),
+        "cannot capitalize an empty name"
+    );
+}
+
+/// ```capnp
+/// struct Map(Key, Value) {
+///   name :Text;
+///   struct Entry { key :Key; value :Value; }
+/// }
+/// struct Name { text :Text; }
+/// struct Point { x :Float64; y :Float64; }
+/// struct Index { entry :Map(Name, Point).Entry; }
+/// ```
+#[test]
+fn golden_nested_generics() {
+    let nodes = [
+        file(0x1000, "index.capnp"),
+        struct_(0x1001, 0x1000, "Map", vec![field("name", Type::Text)]).generic(&["Key", "Value"]),
+        struct_(
+            0x1002,
+            0x1001,
+            "Entry",
+            vec![
+                field("key", Type::Param(0x1001, 0)),
+                field("value", Type::Param(0x1001, 1)),
+            ],
+        ),
+        struct_(0x1003, 0x1000, "Name", vec![field("text", Type::Text)]),
+        struct_(
+            0x1004,
+            0x1000,
+            "Point",
+            vec![field("x", Type::Float64), field("y", Type::Float64)],
+        ),
+        struct_(
+            0x1005,
+            0x1000,
+            "Index",
+            vec![field(
+                "entry",
+                Type::Branded(
+                    0x1002,
+                    0x1001,
+                    vec![Type::Struct(0x1003), Type::Struct(0x1004)],
+                ),
+            )],
+        ),
+    ];
+    check_golden("index_decoder", &nodes, &[]);
+}
+
+/// ```capnp
+/// struct Matrix {
+///   rows :List(List(Float64));
+///   labels :List(List(List(Text)));
+///   grid :List(List(Point));
+/// }
+/// struct Point { x :Float64; y :Float64; }
+/// ```
+fn matrix_schema() -> Vec<Node> {
+    vec![
+        file(0xb000, "matrix.capnp"),
+        struct_(
+            0xb001,
+            0xb000,
+            "Matrix",
+            vec![
+                field("rows", list(list(Type::Float64))),
+                field("labels", list(list(list(Type::Text)))),
+                field("grid", list(list(Type::Struct(0xb002)))),
+            ],
+        ),
+        struct_(
+            0xb002,
+            0xb000,
+            "Point",
+            vec![field("x", Type::Float64), field("y", Type::Float64)],
+        ),
+    ]
+}
+
+#[test]
+fn golden_nested_lists() {
+    check_golden("matrix_decoder", &matrix_schema(), &[]);
+}
+
+/// The schema of `golden_nested_lists`, with `labels` annotated with
+/// `$OCaml.repr("array")`.
+#[test]
+fn golden_seq_lists() {
+    let mut nodes = matrix_schema();
+    let NodeKind::Struct { fields, .. } = &mut nodes[1].kind else {
+        unreachable!()
+    };
+    fields[1].annotations.push(Annotation {
+        id: REPR_ANNOTATION_ID,
+        value: Value::Text("array"),
+    });
+    check_golden("matrix_seq_decoder", &nodes, &["--lists=seq"]);
+}
+
+/// ```capnp
+/// struct Box(T) { value :T; }
+/// struct Book { title :Text; }
+/// struct Shelf { box :Box(Book); boxes :List(Box(Book)); }
+/// ```
+#[test]
+fn golden_brand_bindings() {
+    let nodes = [
+        file(0x2000, "shelf.capnp"),
+        struct_(
+            0x2001,
+            0x2000,
+            "Box",
+            vec![field("value", Type::Param(0x2001, 0))],
+        )
+        .generic(&["T"]),
+        struct_(0x2002, 0x2000, "Book", vec![field("title", Type::Text)]),
+        struct_(
+            0x2003,
+            0x2000,
+            "Shelf",
+            vec![
+                field(
+                    "box",
+                    Type::Branded(0x2001, 0x2001, vec![Type::Struct(0x2002)]),
+                ),
+                field(
+                    "boxes",
+                    list(Type::Branded(0x2001, 0x2001, vec![Type::Struct(0x2002)])),
+                ),
+            ],
+        ),
+    ];
+    check_golden("shelf_decoder", &nodes, &[]);
+}
+
+/// ```capnp
+/// struct Envelope {
+///   payload :AnyPointer; record :AnyStruct; items :AnyList; peer :Capability;
+/// }
+/// ```
+#[test]
+fn golden_untyped_pointers() {
+    let nodes = [
+        file(0x3000, "envelope.capnp"),
+        struct_(
+            0x3001,
+            0x3000,
+            "Envelope",
+            vec![
+                field("payload", Type::AnyPointer),
+                field("record", Type::AnyStruct),
+                field("items", Type::AnyList),
+                field("peer", Type::Capability),
+            ],
+        ),
+    ];
+    check_golden("envelope_decoder", &nodes, &[]);
+}
+
+#[test]
+fn encoders_reject_unbound_generic_arguments() {
+    let nodes = [
+        file(0x2000, "shelf.capnp"),
+        struct_(
+            0x2001,
+            0x2000,
+            "Box",
+            vec![field("value", Type::Param(0x2001, 0))],
+        )
+        .generic(&["T"]),
+        struct_(
+            0x2003,
+            0x2000,
+            "Shelf",
+            vec![field("loose", Type::Struct(0x2001))],
+        ),
+    ];
+    let Err(error) = generate(&nodes, &[]) else {
+        panic!("expected an error");
+    };
+    assert!(error
+        .message
+        .starts_with("untyped pointers can only be encoded as fields"));
+    assert_eq!(error.node.as_deref(), Some("shelf.capnp:Shelf"));
+    assert_eq!(error.member.as_deref(), Some("loose"));
+}
+
+#[test]
+fn converters_reject_untyped_pointers() {
+    for type_ in [Type::AnyPointer, Type::AnyStruct] {
+        let nodes = [
+            file(0x3000, "envelope.capnp"),
+            struct_(0x3001, 0x3000, "Envelope", vec![field("payload", type_)]),
+        ];
+        for format in ["--json", "--sexp"] {
+            let Err(error) = generate(&nodes, &[format]) else {
+                panic!("expected an error");
+            };
+            assert!(error
+                .message
+                .starts_with("untyped pointers cannot be converted"));
+            assert_eq!(error.member.as_deref(), Some("payload"));
+        }
+    }
+}
+
+/// ```capnp
+/// struct Counter {
+///   count :Int64;
+///   total :UInt32 $OCaml.repr("int64");
+///   delta :Int32;
+/// }
+/// ```
+#[test]
+fn golden_int_reprs() {
+    let nodes = [
+        file(0xe000, "counter.capnp"),
+        struct_(
+            0xe001,
+            0xe000,
+            "Counter",
+            vec![
+                field("count", Type::Int64),
+                field("total", Type::Uint32).annotated(REPR_ANNOTATION_ID, Value::Text("int64")),
+                field("delta", Type::Int32),
+            ],
+        ),
+    ];
+    check_golden("counter_decoder", &nodes, &["--result", "--int64=int"]);
+}
+
+/// ```capnp
+/// struct Blob { name :Text; payload :Data; chunks :List(Data); }
+/// ```
+#[test]
+fn golden_text_and_data() {
+    let nodes = [
+        file(0xf000, "blob.capnp"),
+        struct_(
+            0xf001,
+            0xf000,
+            "Blob",
+            vec![
+                field("name", Type::Text),
+                field("payload", Type::Data),
+                field("chunks", list(Type::Data)),
+            ],
+        ),
+    ];
+    check_golden(
+        "blob_decoder",
+        &nodes,
+        &["--result", "--data=bytes", "--validate-utf8"],
+    );
+}
+
+#[test]
+fn errors_name_the_element() {
+    let nodes = [
+        file(0xc000, "points.capnp"),
+        struct_(
+            0xc001,
+            0xc000,
+            "Path",
+            vec![field("length", Type::Float64).annotated(REPR_ANNOTATION_ID, Value::Text("array"))],
+        ),
+    ];
+    let Err(error) = generate(&nodes, &[]) else {
+        panic!("expected an error");
+    };
+    assert_eq!(
+        error.message,
+        "only list fields and 32- and 64-bit integer fields can have a representation"
+    );
+    assert_eq!(error.node.as_deref(), Some("points.capnp:Path"));
+    assert_eq!(error.member.as_deref(), Some("length"));
+}
@@ -0,0 +1,50 @@
+module S = Blob.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type decode_error = string list * [
+  | `Undefined_discriminant of string * int
+  | `Undefined_enumerant of string * int
+  | `Out_of_range of string
+  | `Invalid_utf8
+]
+
+let ( let* ) = Result.bind [@@ocaml.warning "-32"]
+
+let in_field name = Result.map_error (fun (path, e) -> (name :: path, e)) [@@ocaml.warning "-32"]
+
+let decode_list f a =
+  let rec go i acc = function
+    | [] -> Ok (List.rev acc)
+    | x :: xs ->
+      match f x with
+      | Ok y -> go (i + 1) (y :: acc) xs
+      | Error (path, e) -> Error (string_of_int i :: path, e)
+  in
+  go 0 [] (Capnp.Array.to_list a) [@@ocaml.warning "-32"]
+
+let validate_utf_8 s = if String.is_valid_utf_8 s then Ok s else Error ([], `Invalid_utf8) [@@ocaml.warning "-32"]
+
+type blob =
+  {
+    name: string;
+    payload: bytes;
+    chunks: bytes list
+  }
+
+let decode_blob r: (blob, decode_error) result =
+  let* f_name = in_field "name" (validate_utf_8 (R.Blob.name_get r)) in
+  let* f_chunks = in_field "chunks" (decode_list (fun s -> Ok (Bytes.of_string s)) (R.Blob.chunks_get r)) in
+  Ok {
+    name = f_name;
+    payload = Bytes.of_string (R.Blob.payload_get r);
+    chunks = f_chunks
+  }
+
+let encode_blob b (v: blob): unit =
+  B.Blob.name_set b v.name;
+  B.Blob.payload_set b (Bytes.unsafe_to_string v.payload);
+  (let a = B.Blob.chunks_init b (List.length v.chunks) in List.iteri (fun i v' -> Capnp.Array.set a i (Bytes.unsafe_to_string v')) v.chunks)
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_blob: blob = Result.get_ok (decode_blob (R.Blob.of_message empty_message))
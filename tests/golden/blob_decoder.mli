@@ -0,0 +1,21 @@
+module S := Blob.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type decode_error = string list * [
+  | `Undefined_discriminant of string * int
+  | `Undefined_enumerant of string * int
+  | `Out_of_range of string
+  | `Invalid_utf8
+]
+
+type blob =
+  {
+    name: string;
+    payload: bytes;
+    chunks: bytes list
+  }
+
+val decode_blob: R.Blob.t -> (blob, decode_error) result
+val encode_blob: B.Blob.t -> blob -> unit
+val default_blob: blob
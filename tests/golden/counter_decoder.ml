@@ -0,0 +1,77 @@
+module S = Counter.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type decode_error = string list * [
+  | `Undefined_discriminant of string * int
+  | `Undefined_enumerant of string * int
+  | `Out_of_range of string
+  | `Invalid_utf8
+]
+
+let ( let* ) = Result.bind [@@ocaml.warning "-32"]
+
+let in_field name = Result.map_error (fun (path, e) -> (name :: path, e)) [@@ocaml.warning "-32"]
+
+let decode_list f a =
+  let rec go i acc = function
+    | [] -> Ok (List.rev acc)
+    | x :: xs ->
+      match f x with
+      | Ok y -> go (i + 1) (y :: acc) xs
+      | Error (path, e) -> Error (string_of_int i :: path, e)
+  in
+  go 0 [] (Capnp.Array.to_list a) [@@ocaml.warning "-32"]
+
+module Int_repr = struct
+  let fail type_name value = invalid_arg (value ^ " is out of the range of " ^ type_name)
+  let checked f n = match f n with x -> Ok x | exception Invalid_argument e -> Error ([], `Out_of_range e)
+  let int_of_int64 n =
+    if Int64.compare n (Int64.of_int min_int) < 0 || Int64.compare n (Int64.of_int max_int) > 0
+    then fail "int" (Int64.to_string n)
+    else Int64.to_int n
+  let int_of_uint64 n =
+    if Stdint.Uint64.compare n (Stdint.Uint64.of_int max_int) > 0
+    then fail "int" (Stdint.Uint64.to_string n)
+    else Stdint.Uint64.to_int n
+  let int64_of_uint64 n =
+    if Stdint.Uint64.compare n (Stdint.Uint64.of_int64 Int64.max_int) > 0
+    then fail "int64" (Stdint.Uint64.to_string n)
+    else Stdint.Uint64.to_int64 n
+  let int32_of_int n =
+    if n < Int32.to_int Int32.min_int || n > Int32.to_int Int32.max_int
+    then fail "Int32" (string_of_int n)
+    else Int32.of_int n
+  let uint32_of_int n =
+    if n < 0 || n > 0xffff_ffff then fail "UInt32" (string_of_int n) else Stdint.Uint32.of_int n
+  let uint32_of_int64 n =
+    if Int64.compare n 0L < 0 || Int64.compare n 0xffff_ffffL > 0
+    then fail "UInt32" (Int64.to_string n)
+    else Stdint.Uint32.of_int64 n
+  let uint64_of_int n = if n < 0 then fail "UInt64" (string_of_int n) else Stdint.Uint64.of_int n
+  let uint64_of_int64 n =
+    if Int64.compare n 0L < 0 then fail "UInt64" (Int64.to_string n) else Stdint.Uint64.of_int64 n
+end [@@ocaml.warning "-32"]
+
+type counter =
+  {
+    count: int;
+    total: int64;
+    delta: int32
+  }
+
+let decode_counter r: (counter, decode_error) result =
+  let* f_count = in_field "count" (Int_repr.checked Int_repr.int_of_int64 (R.Counter.count_get r)) in
+  Ok {
+    count = f_count;
+    total = Stdint.Uint32.to_int64 (R.Counter.total_get r);
+    delta = (R.Counter.delta_get r)
+  }
+
+let encode_counter b (v: counter): unit =
+  B.Counter.count_set b (Int64.of_int v.count);
+  B.Counter.total_set b (Int_repr.uint32_of_int64 v.total);
+  B.Counter.delta_set b v.delta
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_counter: counter = Result.get_ok (decode_counter (R.Counter.of_message empty_message))
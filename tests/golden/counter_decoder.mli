@@ -0,0 +1,21 @@
+module S := Counter.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type decode_error = string list * [
+  | `Undefined_discriminant of string * int
+  | `Undefined_enumerant of string * int
+  | `Out_of_range of string
+  | `Invalid_utf8
+]
+
+type counter =
+  {
+    count: int;
+    total: int64;
+    delta: int32
+  }
+
+val decode_counter: R.Counter.t -> (counter, decode_error) result
+val encode_counter: B.Counter.t -> counter -> unit
+val default_counter: counter
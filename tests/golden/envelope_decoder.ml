@@ -0,0 +1,45 @@
+module S = Envelope.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+(** An untyped pointer: an AnyPointer value, or an unbound generic argument. *)
+type any_pointer = R.pointer_t
+
+(** The value of an AnyStruct field: a pointer to a struct of any type. *)
+type any_struct = Any_struct of R.pointer_t [@@unboxed]
+
+(** The value of an AnyList field: a pointer to a list of any type. *)
+type any_list = Any_list of R.pointer_t [@@unboxed]
+
+(** The value of a Capability field: a pointer to a capability of any type. *)
+type any_capability = Any_capability of R.pointer_t [@@unboxed]
+
+type envelope =
+  {
+    payload: any_pointer;
+    record: any_struct;
+    items: any_list;
+    peer: any_capability
+  }
+
+let decode_envelope r: envelope =
+  {
+    payload = (R.Envelope.payload_get r);
+    record = Any_struct (R.Envelope.record_get r);
+    items = Any_list (R.Envelope.items_get r);
+    peer = Any_capability (R.Envelope.peer_get r)
+  }
+
+let any_pointer_as decode (p: any_pointer) = decode p
+let any_struct_as decode (Any_struct p) = decode (R.of_pointer p)
+let any_list_as decode (Any_list p) = decode p
+let any_capability_as decode (Any_capability p) = decode p
+
+let encode_envelope b (v: envelope): unit =
+  ignore (B.Envelope.payload_set_reader b v.payload);
+  ignore (B.Envelope.record_set_reader b (let (Any_struct p) = v.record in p));
+  ignore (B.Envelope.items_set_reader b (let (Any_list p) = v.items in p));
+  ignore (B.Envelope.peer_set_reader b (let (Any_capability p) = v.peer in p))
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_envelope: envelope = decode_envelope (R.Envelope.of_message empty_message)
@@ -0,0 +1,31 @@
+module S := Envelope.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+(** An untyped pointer: an AnyPointer value, or an unbound generic argument. *)
+type any_pointer = R.pointer_t
+
+(** The value of an AnyStruct field: a pointer to a struct of any type. *)
+type any_struct = Any_struct of R.pointer_t [@@unboxed]
+
+(** The value of an AnyList field: a pointer to a list of any type. *)
+type any_list = Any_list of R.pointer_t [@@unboxed]
+
+(** The value of a Capability field: a pointer to a capability of any type. *)
+type any_capability = Any_capability of R.pointer_t [@@unboxed]
+
+type envelope =
+  {
+    payload: any_pointer;
+    record: any_struct;
+    items: any_list;
+    peer: any_capability
+  }
+
+val any_pointer_as: (R.pointer_t -> 't) -> any_pointer -> 't
+val any_struct_as: ('a S.reader_t -> 't) -> any_struct -> 't
+val any_list_as: (R.pointer_t -> 't) -> any_list -> 't
+val any_capability_as: (R.pointer_t -> 't) -> any_capability -> 't
+val decode_envelope: R.Envelope.t -> envelope
+val encode_envelope: B.Envelope.t -> envelope -> unit
+val default_envelope: envelope
@@ -0,0 +1,81 @@
+module S = Index.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type ('key, 'value) map_entry =
+  {
+    key: 'key;
+    value: 'value
+  }
+
+type ('key, 'value) map =
+  {
+    name: string
+  }
+
+type name =
+  {
+    text: string
+  }
+
+type point =
+  {
+    x: float;
+    y: float
+  }
+
+type index =
+  {
+    entry: (name, point) map_entry
+  }
+
+let decode_map_entry: 'key 'value. (R.pointer_t -> 'key) -> (R.pointer_t -> 'value) -> R.Map.Entry.t -> ('key, 'value) map_entry = fun decode_key decode_value r ->
+  {
+    key = decode_key (R.Map.Entry.key_get r);
+    value = decode_value (R.Map.Entry.value_get r)
+  }
+
+let decode_map: 'key 'value. (R.pointer_t -> 'key) -> (R.pointer_t -> 'value) -> R.Map.t -> ('key, 'value) map = fun decode_key decode_value r ->
+  {
+    name = (R.Map.name_get r)
+  }
+
+let decode_name r: name =
+  {
+    text = (R.Name.text_get r)
+  }
+
+let decode_point r: point =
+  {
+    x = (R.Point.x_get r);
+    y = (R.Point.y_get r)
+  }
+
+let decode_index r: index =
+  {
+    entry = decode_map_entry (fun p -> decode_name (R.of_pointer p)) (fun p -> decode_point (R.of_pointer p)) (R.Index.entry_get r)
+  }
+
+let encode_map_entry: 'key 'value. (B.pointer_t -> 'key -> unit) -> (B.pointer_t -> 'value -> unit) -> B.Map.Entry.t -> ('key, 'value) map_entry -> unit = fun encode_key encode_value b v ->
+  encode_key (B.Map.Entry.key_get b) v.key;
+  encode_value (B.Map.Entry.value_get b) v.value
+
+let encode_map: 'key 'value. (B.pointer_t -> 'key -> unit) -> (B.pointer_t -> 'value -> unit) -> B.Map.t -> ('key, 'value) map -> unit = fun encode_key encode_value b v ->
+  B.Map.name_set b v.name
+
+let encode_name b (v: name): unit =
+  B.Name.text_set b v.text
+
+let encode_point b (v: point): unit =
+  B.Point.x_set b v.x;
+  B.Point.y_set b v.y
+
+let encode_index b (v: index): unit =
+  encode_map_entry (fun p v -> encode_name (B.Name.init_pointer p) v) (fun p v -> encode_point (B.Point.init_pointer p) v) (B.Index.entry_init b) v.entry
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_map_entry decode_key decode_value = decode_map_entry decode_key decode_value (R.Map.Entry.of_message empty_message)
+let default_map decode_key decode_value = decode_map decode_key decode_value (R.Map.of_message empty_message)
+let default_name: name = decode_name (R.Name.of_message empty_message)
+let default_point: point = decode_point (R.Point.of_message empty_message)
+let default_index: index = decode_index (R.Index.of_message empty_message)
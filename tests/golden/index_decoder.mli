@@ -0,0 +1,46 @@
+module S := Index.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type ('key, 'value) map_entry =
+  {
+    key: 'key;
+    value: 'value
+  }
+
+type ('key, 'value) map =
+  {
+    name: string
+  }
+
+type name =
+  {
+    text: string
+  }
+
+type point =
+  {
+    x: float;
+    y: float
+  }
+
+type index =
+  {
+    entry: (name, point) map_entry
+  }
+
+val decode_map_entry: (R.pointer_t -> 'key) -> (R.pointer_t -> 'value) -> R.Map.Entry.t -> ('key, 'value) map_entry
+val encode_map_entry: (B.pointer_t -> 'key -> unit) -> (B.pointer_t -> 'value -> unit) -> B.Map.Entry.t -> ('key, 'value) map_entry -> unit
+val default_map_entry: (R.pointer_t -> 'key) -> (R.pointer_t -> 'value) -> ('key, 'value) map_entry
+val decode_map: (R.pointer_t -> 'key) -> (R.pointer_t -> 'value) -> R.Map.t -> ('key, 'value) map
+val encode_map: (B.pointer_t -> 'key -> unit) -> (B.pointer_t -> 'value -> unit) -> B.Map.t -> ('key, 'value) map -> unit
+val default_map: (R.pointer_t -> 'key) -> (R.pointer_t -> 'value) -> ('key, 'value) map
+val decode_name: R.Name.t -> name
+val encode_name: B.Name.t -> name -> unit
+val default_name: name
+val decode_point: R.Point.t -> point
+val encode_point: B.Point.t -> point -> unit
+val default_point: point
+val decode_index: R.Index.t -> index
+val encode_index: B.Index.t -> index -> unit
+val default_index: index
@@ -0,0 +1,42 @@
+module S = Matrix.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type point =
+  {
+    x: float;
+    y: float
+  }
+
+type matrix =
+  {
+    rows: float list list;
+    labels: string list list list;
+    grid: point list list
+  }
+
+let decode_point r: point =
+  {
+    x = (R.Point.x_get r);
+    y = (R.Point.y_get r)
+  }
+
+let decode_matrix r: matrix =
+  {
+    rows = Capnp.Array.map_list (R.Matrix.rows_get r) ~f:Capnp.Array.to_list;
+    labels = Capnp.Array.map_list (R.Matrix.labels_get r) ~f:(fun a -> Capnp.Array.map_list a ~f:Capnp.Array.to_list);
+    grid = Capnp.Array.map_list (R.Matrix.grid_get r) ~f:(fun a -> Capnp.Array.map_list a ~f:decode_point)
+  }
+
+let encode_point b (v: point): unit =
+  B.Point.x_set b v.x;
+  B.Point.y_set b v.y
+
+let encode_matrix b (v: matrix): unit =
+  (let a = B.Matrix.rows_init b (List.length v.rows) in List.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (List.length v'); Capnp.Array.get a i) in List.iteri (fun i v' -> Capnp.Array.set a i v') v') v.rows);
+  (let a = B.Matrix.labels_init b (List.length v.labels) in List.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (List.length v'); Capnp.Array.get a i) in List.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (List.length v'); Capnp.Array.get a i) in List.iteri (fun i v' -> Capnp.Array.set a i v') v') v') v.labels);
+  (let a = B.Matrix.grid_init b (List.length v.grid) in List.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (List.length v'); Capnp.Array.get a i) in List.iteri (fun i v' -> encode_point (Capnp.Array.get a i) v') v') v.grid)
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_matrix: matrix = decode_matrix (R.Matrix.of_message empty_message)
+let default_point: point = decode_point (R.Point.of_message empty_message)
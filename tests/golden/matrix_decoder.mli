@@ -0,0 +1,23 @@
+module S := Matrix.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type point =
+  {
+    x: float;
+    y: float
+  }
+
+type matrix =
+  {
+    rows: float list list;
+    labels: string list list list;
+    grid: point list list
+  }
+
+val decode_matrix: R.Matrix.t -> matrix
+val encode_matrix: B.Matrix.t -> matrix -> unit
+val default_matrix: matrix
+val decode_point: R.Point.t -> point
+val encode_point: B.Point.t -> point -> unit
+val default_point: point
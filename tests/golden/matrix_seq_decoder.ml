@@ -0,0 +1,42 @@
+module S = Matrix.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type point =
+  {
+    x: float;
+    y: float
+  }
+
+type matrix =
+  {
+    rows: float Seq.t Seq.t;
+    labels: string Seq.t Seq.t array;
+    grid: point Seq.t Seq.t
+  }
+
+let decode_point r: point =
+  {
+    x = (R.Point.x_get r);
+    y = (R.Point.y_get r)
+  }
+
+let decode_matrix r: matrix =
+  {
+    rows = (let a = (R.Matrix.rows_get r) in Seq.init (Capnp.Array.length a) (fun i -> (fun a -> Seq.init (Capnp.Array.length a) (Capnp.Array.get a)) (Capnp.Array.get a i)));
+    labels = Capnp.Array.map_array (R.Matrix.labels_get r) ~f:(fun a -> Seq.init (Capnp.Array.length a) (fun i -> (fun a -> Seq.init (Capnp.Array.length a) (Capnp.Array.get a)) (Capnp.Array.get a i)));
+    grid = (let a = (R.Matrix.grid_get r) in Seq.init (Capnp.Array.length a) (fun i -> (fun a -> Seq.init (Capnp.Array.length a) (fun i -> decode_point (Capnp.Array.get a i))) (Capnp.Array.get a i)))
+  }
+
+let encode_point b (v: point): unit =
+  B.Point.x_set b v.x;
+  B.Point.y_set b v.y
+
+let encode_matrix b (v: matrix): unit =
+  (let a = B.Matrix.rows_init b (Seq.length v.rows) in Seq.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (Seq.length v'); Capnp.Array.get a i) in Seq.iteri (fun i v' -> Capnp.Array.set a i v') v') v.rows);
+  (let a = B.Matrix.labels_init b (Array.length v.labels) in Array.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (Seq.length v'); Capnp.Array.get a i) in Seq.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (Seq.length v'); Capnp.Array.get a i) in Seq.iteri (fun i v' -> Capnp.Array.set a i v') v') v') v.labels);
+  (let a = B.Matrix.grid_init b (Seq.length v.grid) in Seq.iteri (fun i v' -> let a = (Capnp.Array.init (Capnp.Array.get a i) (Seq.length v'); Capnp.Array.get a i) in Seq.iteri (fun i v' -> encode_point (Capnp.Array.get a i) v') v') v.grid)
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_matrix: matrix = decode_matrix (R.Matrix.of_message empty_message)
+let default_point: point = decode_point (R.Point.of_message empty_message)
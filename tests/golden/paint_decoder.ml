@@ -0,0 +1,177 @@
+module S = Paint.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+module Json_codec = struct
+  exception Invalid of string
+  let fail what j = raise (Invalid (what ^ ": " ^ Yojson.Safe.to_string j))
+  let wrap f = try Ok (f ()) with Invalid e -> Error e
+  let get f j = match f j with Ok x -> x | Error e -> raise (Invalid e)
+  let lift f j = wrap (fun () -> f j)
+  let default d f = function `Null -> d () | j -> f j
+  let unit () = `Null
+  let bool b = `Bool b
+  let int n = `Int n
+  let int32 n = `Int (Int32.to_int n)
+  let int64 n = `String (Int64.to_string n)
+  let uint32 n = `Int (Stdint.Uint32.to_int n)
+  let uint64 n = `String (Stdint.Uint64.to_string n)
+  let float x =
+    if Float.is_nan x then `String "NaN"
+    else if x = Float.infinity then `String "Infinity"
+    else if x = Float.neg_infinity then `String "-Infinity"
+    else `Float x
+  let string s = `String s
+  let data s = `List (List.init (String.length s) (fun i -> `Int (Char.code s.[i])))
+  let list f xs = `List (List.map f xs)
+  let array f xs = list f (Array.to_list xs)
+  let seq f xs = list f (List.of_seq xs)
+  let option f = function Some x -> f x | None -> `Null
+  let capability _ = `Null
+  let enumerant name = `String name
+  let obj members = `Assoc members
+  let field name = function
+    | `Assoc members -> Option.value (List.assoc_opt name members) ~default:`Null
+    | `Null -> `Null
+    | j -> fail "expected an object" j
+  let member names = function
+    | `Assoc members as j ->
+      (match List.find_opt (fun name -> List.mem_assoc name members) names with
+       | Some name -> name
+       | None -> fail "expected a union member" j)
+    | `Null -> ""
+    | j -> fail "expected an object" j
+  let to_unit _ = ()
+  let to_bool = function `Bool b -> b | `Null -> false | j -> fail "expected a boolean" j
+  let to_int = function `Int n -> n | `Null -> 0 | j -> fail "expected an integer" j
+  let to_int32 j =
+    let n = to_int j in
+    if Int32.to_int (Int32.of_int n) = n then Int32.of_int n
+    else fail "expected a 32-bit integer" j
+  let to_int64 = function
+    | (`String s | `Intlit s) as j ->
+      (match Int64.of_string_opt s with Some n -> n | None -> fail "expected a 64-bit integer" j)
+    | `Int n -> Int64.of_int n
+    | `Null -> 0L
+    | j -> fail "expected a 64-bit integer" j
+  let to_uint32 j =
+    let n = to_int j in
+    if n >= 0 && n <= 0xffff_ffff then Stdint.Uint32.of_int n
+    else fail "expected an unsigned 32-bit integer" j
+  let to_uint64 = function
+    | (`String s | `Intlit s) as j ->
+      (try Stdint.Uint64.of_string s with _ -> fail "expected an unsigned 64-bit integer" j)
+    | `Int n when n >= 0 -> Stdint.Uint64.of_int n
+    | `Null -> Stdint.Uint64.zero
+    | j -> fail "expected an unsigned 64-bit integer" j
+  let to_float = function
+    | `Float x -> x
+    | `Int n -> float_of_int n
+    | `String "NaN" -> Float.nan
+    | `String "Infinity" -> Float.infinity
+    | `String "-Infinity" -> Float.neg_infinity
+    | `Null -> 0.
+    | j -> fail "expected a number" j
+  let to_string = function `String s -> s | `Null -> "" | j -> fail "expected a string" j
+  let to_byte = function
+    | `Int n when n >= 0 && n < 256 -> Char.chr n
+    | j -> fail "expected a byte" j
+  let to_data = function
+    | `List js -> String.of_seq (Seq.map to_byte (List.to_seq js))
+    | `Null -> ""
+    | j -> fail "expected an array of bytes" j
+  let to_list f = function `List js -> List.map f js | `Null -> [] | j -> fail "expected an array" j
+  let to_array f j = Array.of_list (to_list f j)
+  let to_seq f j = List.to_seq (to_list f j)
+  let to_option f = function `Null -> None | j -> Some (f j)
+  let to_capability _ = None
+  let to_enumerant = function
+    | `String name -> `Name name
+    | `Int n -> `Ordinal n
+    | `Null -> `Ordinal 0
+    | j -> fail "expected an enumerant" j
+end [@@ocaml.warning "-32"]
+
+type color =
+  | Red
+  | Green
+  | Unknown of int
+
+type paint =
+  {
+    color: color;
+    shades: color list;
+    union: paint_union
+  }
+and paint_union =
+  | Matte
+  | Gloss of float
+  | Unknown of int
+
+let decode_color (r: R.Color.t): color = match r with
+  | Red -> Red
+  | Green -> Green
+  | Undefined n -> Unknown n
+
+let rec decode_paint r: paint =
+  {
+    color = decode_color (R.Paint.color_get r);
+    shades = Capnp.Array.map_list (R.Paint.shades_get r) ~f:decode_color;
+    union = decode_paint_union r
+  }
+and decode_paint_union r: paint_union =
+  match R.Paint.get r with
+  | Matte -> Matte
+  | Gloss r' -> Gloss (r')
+  | Undefined n -> Unknown n
+
+let encode_color (v: color): B.Color.t = match v with
+  | Red -> Red
+  | Green -> Green
+  | Unknown n -> Undefined n
+
+let rec encode_paint b (v: paint): unit =
+  B.Paint.color_set_unsafe b (encode_color v.color);
+  (let a = B.Paint.shades_init b (List.length v.shades) in List.iteri (fun i v' -> Capnp.Array.set a i (match (v': color) with Unknown _ -> invalid_arg "Unknown enumerant in a list" | v' -> encode_color v')) v.shades);
+  encode_paint_union b v.union
+and encode_paint_union b (v: paint_union): unit =
+  match v with
+  | Matte -> B.Paint.matte_set b
+  | Gloss v' -> B.Paint.gloss_set b v'
+  | Unknown _ -> invalid_arg "Unknown discriminant"
+
+let color_to_yojson (v: color): Yojson.Safe.t = match v with
+  | Red -> Json_codec.enumerant "red"
+  | Green -> Json_codec.enumerant "green"
+  | Unknown n -> Json_codec.int n
+and color_of_yojson (j: Yojson.Safe.t): (color, string) result =
+  Json_codec.wrap (fun () : color ->
+    match Json_codec.to_enumerant j with
+    | `Name "red" | `Ordinal 0 -> Red
+    | `Name "green" | `Ordinal 1 -> Green
+    | `Ordinal n -> Unknown n
+    | _ -> Json_codec.fail "undefined enumerant" j)
+
+let paint_to_yojson (v: paint): Yojson.Safe.t =
+  Json_codec.obj [
+    ("color", color_to_yojson v.color);
+    ("shades", (Json_codec.list color_to_yojson) v.shades);
+    (match v.union with
+      | Matte -> ("matte", Json_codec.unit ())
+      | Gloss v' -> ("gloss", Json_codec.float v')
+      | Unknown n -> ("unknown", Json_codec.int n))
+  ]
+and paint_of_yojson (j: Yojson.Safe.t): (paint, string) result =
+  Json_codec.wrap (fun () : paint ->
+    {
+      color = (Json_codec.get color_of_yojson) (Json_codec.field "color" j);
+      shades = (Json_codec.to_list (Json_codec.get color_of_yojson)) (Json_codec.field "shades" j);
+      union = (match Json_codec.member ["matte"; "gloss"; "unknown"] j with
+      | "gloss" -> Gloss (Json_codec.to_float (Json_codec.field "gloss" j))
+      | "unknown" -> Unknown (Json_codec.to_int (Json_codec.field "unknown" j))
+      | "matte" | "" -> Matte
+      | _ -> Json_codec.fail "undefined union member" j)
+    })
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_paint: paint = decode_paint (R.Paint.of_message empty_message)
@@ -0,0 +1,31 @@
+module S := Paint.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type color =
+  | Red
+  | Green
+  | Unknown of int
+
+type paint =
+  {
+    color: color;
+    shades: color list;
+    union: paint_union
+  }
+and paint_union =
+  | Matte
+  | Gloss of float
+  | Unknown of int
+
+val decode_color: R.Color.t -> color
+val encode_color: color -> B.Color.t
+val color_to_yojson: color -> Yojson.Safe.t
+val color_of_yojson: Yojson.Safe.t -> (color, string) result
+val decode_paint: R.Paint.t -> paint
+val encode_paint: B.Paint.t -> paint -> unit
+val decode_paint_union: R.Paint.t -> paint_union
+val encode_paint_union: B.Paint.t -> paint_union -> unit
+val paint_to_yojson: paint -> Yojson.Safe.t
+val paint_of_yojson: Yojson.Safe.t -> (paint, string) result
+val default_paint: paint
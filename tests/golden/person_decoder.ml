@@ -0,0 +1,28 @@
+module S = Person.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type contact =
+  {
+    full_name: string;
+    tags: string array;
+    street: string;
+    city: string
+  }
+
+let decode_contact r: contact =
+  {
+    full_name = (R.Person.name_get r);
+    tags = Capnp.Array.to_array (R.Person.tags_get r);
+    street = (R.Person.Address.street_get (R.Person.address_get r));
+    city = (R.Person.Address.city_get (R.Person.address_get r))
+  }
+
+let encode_contact b (v: contact): unit =
+  B.Person.name_set b v.full_name;
+  (let a = B.Person.tags_init b (Array.length v.tags) in Array.iteri (fun i v' -> Capnp.Array.set a i v') v.tags);
+  B.Person.Address.street_set (B.Person.address_get b) v.street;
+  B.Person.Address.city_set (B.Person.address_get b) v.city
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_contact: contact = decode_contact (R.Person.of_message empty_message)
@@ -0,0 +1,15 @@
+module S := Person.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type contact =
+  {
+    full_name: string;
+    tags: string array;
+    street: string;
+    city: string
+  }
+
+val decode_contact: R.Person.t -> contact
+val encode_contact: B.Person.t -> contact -> unit
+val default_contact: contact
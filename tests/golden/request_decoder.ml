@@ -0,0 +1,49 @@
+module S = Request.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type method_ =
+  | Get
+  | Post
+
+type header =
+  {
+    name: string
+  }
+
+type request =
+  {
+    method_: method_;
+    headers: header list
+  }
+
+let decode_method (r: R.Method.t): method_ = match r with
+  | Get -> Get
+  | Post -> Post
+  | Undefined _ -> failwith "Undefined enumerant"
+
+let decode_header r: header =
+  {
+    name = (R.Header.name_get r)
+  }
+
+let decode_request r: request =
+  {
+    method_ = decode_method (R.Request.method_get r);
+    headers = Capnp.Array.map_list (R.Request.headers_get r) ~f:decode_header
+  }
+
+let encode_method (v: method_): B.Method.t = match v with
+  | Get -> Get
+  | Post -> Post
+
+let encode_header b (v: header): unit =
+  B.Header.name_set b v.name
+
+let encode_request b (v: request): unit =
+  B.Request.method_set b (encode_method v.method_);
+  (let a = B.Request.headers_init b (List.length v.headers) in List.iteri (fun i v' -> encode_header (Capnp.Array.get a i) v') v.headers)
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_request: request = decode_request (R.Request.of_message empty_message)
+let default_header: header = decode_header (R.Header.of_message empty_message)
@@ -0,0 +1,30 @@
+module S := Request.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type method_ =
+  | Get
+  | Post
+[@@deriving show, eq]
+
+type header =
+  {
+    name: string
+  }
+[@@deriving show, eq]
+
+type request =
+  {
+    method_: method_;
+    headers: header list
+  }
+[@@deriving show, eq]
+
+val decode_method: R.Method.t -> method_
+val encode_method: method_ -> B.Method.t
+val decode_request: R.Request.t -> request
+val encode_request: B.Request.t -> request -> unit
+val default_request: request
+val decode_header: R.Header.t -> header
+val encode_header: B.Header.t -> header -> unit
+val default_header: header
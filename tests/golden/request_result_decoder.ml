@@ -0,0 +1,72 @@
+module S = Request.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type decode_error = string list * [
+  | `Undefined_discriminant of string * int
+  | `Undefined_enumerant of string * int
+  | `Out_of_range of string
+  | `Invalid_utf8
+]
+
+let ( let* ) = Result.bind [@@ocaml.warning "-32"]
+
+let in_field name = Result.map_error (fun (path, e) -> (name :: path, e)) [@@ocaml.warning "-32"]
+
+let decode_list f a =
+  let rec go i acc = function
+    | [] -> Ok (List.rev acc)
+    | x :: xs ->
+      match f x with
+      | Ok y -> go (i + 1) (y :: acc) xs
+      | Error (path, e) -> Error (string_of_int i :: path, e)
+  in
+  go 0 [] (Capnp.Array.to_list a) [@@ocaml.warning "-32"]
+
+type method_ =
+  | Get
+  | Post
+
+type header =
+  {
+    name: string
+  }
+
+type request =
+  {
+    method_: method_;
+    headers: header list
+  }
+
+let decode_method (r: R.Method.t): (method_, decode_error) result = match r with
+  | Get -> Ok Get
+  | Post -> Ok Post
+  | Undefined n -> Error ([], `Undefined_enumerant ("Method", n))
+
+let decode_header r: (header, decode_error) result =
+  Ok {
+    name = (R.Header.name_get r)
+  }
+
+let decode_request r: (request, decode_error) result =
+  let* f_method_ = in_field "method" (decode_method (R.Request.method_get r)) in
+  let* f_headers = in_field "headers" (decode_list decode_header (R.Request.headers_get r)) in
+  Ok {
+    method_ = f_method_;
+    headers = f_headers
+  }
+
+let encode_method (v: method_): B.Method.t = match v with
+  | Get -> Get
+  | Post -> Post
+
+let encode_header b (v: header): unit =
+  B.Header.name_set b v.name
+
+let encode_request b (v: request): unit =
+  B.Request.method_set b (encode_method v.method_);
+  (let a = B.Request.headers_init b (List.length v.headers) in List.iteri (fun i v' -> encode_header (Capnp.Array.get a i) v') v.headers)
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_request: request = Result.get_ok (decode_request (R.Request.of_message empty_message))
+let default_header: header = Result.get_ok (decode_header (R.Header.of_message empty_message))
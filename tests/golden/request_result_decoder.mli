@@ -0,0 +1,34 @@
+module S := Request.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type decode_error = string list * [
+  | `Undefined_discriminant of string * int
+  | `Undefined_enumerant of string * int
+  | `Out_of_range of string
+  | `Invalid_utf8
+]
+
+type method_ =
+  | Get
+  | Post
+
+type header =
+  {
+    name: string
+  }
+
+type request =
+  {
+    method_: method_;
+    headers: header list
+  }
+
+val decode_method: R.Method.t -> (method_, decode_error) result
+val encode_method: method_ -> B.Method.t
+val decode_request: R.Request.t -> (request, decode_error) result
+val encode_request: B.Request.t -> request -> unit
+val default_request: request
+val decode_header: R.Header.t -> (header, decode_error) result
+val encode_header: B.Header.t -> header -> unit
+val default_header: header
@@ -0,0 +1,150 @@
+module S = Request.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+module Sexp_codec = struct
+  open Sexplib0
+  let fail what sexp = Sexp_conv.of_sexp_error what sexp
+  let wrap f = f ()
+  let get f sexp = f sexp
+  let lift f = f
+  let is_null = function Sexp.List [] -> true | _ -> false
+  let default d f sexp = if is_null sexp then d () else f sexp
+  let unit () = Sexp.List []
+  let bool = Sexp_conv.sexp_of_bool
+  let int = Sexp_conv.sexp_of_int
+  let int32 = Sexp_conv.sexp_of_int32
+  let int64 = Sexp_conv.sexp_of_int64
+  let uint32 n = Sexp.Atom (Stdint.Uint32.to_string n)
+  let uint64 n = Sexp.Atom (Stdint.Uint64.to_string n)
+  let float = Sexp_conv.sexp_of_float
+  let string = Sexp_conv.sexp_of_string
+  let data = Sexp_conv.sexp_of_string
+  let list = Sexp_conv.sexp_of_list
+  let array = Sexp_conv.sexp_of_array
+  let seq f xs = list f (List.of_seq xs)
+  let option f = function Some x -> f x | None -> Sexp.List []
+  let capability _ = Sexp.List []
+  let enumerant name = Sexp.Atom name
+  let obj members =
+    Sexp.List (List.map (fun (name, sexp) -> Sexp.List [Sexp.Atom name; sexp]) members)
+  let members = function
+    | Sexp.List sexps as sexp ->
+      List.map (function
+        | Sexp.List [Sexp.Atom name; value] -> (name, value)
+        | _ -> fail "expected a list of members" sexp) sexps
+    | sexp -> fail "expected a list of members" sexp
+  let field name sexp = Option.value (List.assoc_opt name (members sexp)) ~default:(Sexp.List [])
+  let member names sexp =
+    if is_null sexp then ""
+    else
+      let members = members sexp in
+      match List.find_opt (fun name -> List.mem_assoc name members) names with
+      | Some name -> name
+      | None -> fail "expected a union member" sexp
+  let to_unit _ = ()
+  let to_bool = default (fun () -> false) Sexp_conv.bool_of_sexp
+  let to_int = default (fun () -> 0) Sexp_conv.int_of_sexp
+  let to_int32 = default (fun () -> 0l) Sexp_conv.int32_of_sexp
+  let to_int64 = default (fun () -> 0L) Sexp_conv.int64_of_sexp
+  let of_atom what of_string = function
+    | Sexp.Atom s as sexp -> (try of_string s with _ -> fail what sexp)
+    | sexp -> fail what sexp
+  let to_uint32 =
+    default (fun () -> Stdint.Uint32.zero)
+      (of_atom "expected an unsigned 32-bit integer" Stdint.Uint32.of_string)
+  let to_uint64 =
+    default (fun () -> Stdint.Uint64.zero)
+      (of_atom "expected an unsigned 64-bit integer" Stdint.Uint64.of_string)
+  let to_float = default (fun () -> 0.) Sexp_conv.float_of_sexp
+  let to_string = default (fun () -> "") Sexp_conv.string_of_sexp
+  let to_data = to_string
+  let to_list = Sexp_conv.list_of_sexp
+  let to_array = Sexp_conv.array_of_sexp
+  let to_seq f sexp = List.to_seq (to_list f sexp)
+  let to_option f sexp = if is_null sexp then None else Some (f sexp)
+  let to_capability _ = None
+  let to_enumerant = function
+    | Sexp.Atom name ->
+      (match int_of_string_opt name with Some n -> `Ordinal n | None -> `Name name)
+    | Sexp.List [] -> `Ordinal 0
+    | sexp -> fail "expected an enumerant" sexp
+end [@@ocaml.warning "-32"]
+
+type method_ =
+  | Get
+  | Post
+
+type header =
+  {
+    name: string
+  }
+
+type request =
+  {
+    method_: method_;
+    headers: header list
+  }
+
+let decode_method (r: R.Method.t): method_ = match r with
+  | Get -> Get
+  | Post -> Post
+  | Undefined _ -> failwith "Undefined enumerant"
+
+let decode_header r: header =
+  {
+    name = (R.Header.name_get r)
+  }
+
+let decode_request r: request =
+  {
+    method_ = decode_method (R.Request.method_get r);
+    headers = Capnp.Array.map_list (R.Request.headers_get r) ~f:decode_header
+  }
+
+let encode_method (v: method_): B.Method.t = match v with
+  | Get -> Get
+  | Post -> Post
+
+let encode_header b (v: header): unit =
+  B.Header.name_set b v.name
+
+let encode_request b (v: request): unit =
+  B.Request.method_set b (encode_method v.method_);
+  (let a = B.Request.headers_init b (List.length v.headers) in List.iteri (fun i v' -> encode_header (Capnp.Array.get a i) v') v.headers)
+
+let sexp_of_method (v: method_): Sexplib0.Sexp.t = match v with
+  | Get -> Sexp_codec.enumerant "get"
+  | Post -> Sexp_codec.enumerant "post"
+and method_of_sexp (j: Sexplib0.Sexp.t): method_ =
+  Sexp_codec.wrap (fun () : method_ ->
+    match Sexp_codec.to_enumerant j with
+    | `Name "get" | `Ordinal 0 -> Get
+    | `Name "post" | `Ordinal 1 -> Post
+    | _ -> Sexp_codec.fail "undefined enumerant" j)
+
+let sexp_of_header (v: header): Sexplib0.Sexp.t =
+  Sexp_codec.obj [
+    ("name", Sexp_codec.string v.name)
+  ]
+and header_of_sexp (j: Sexplib0.Sexp.t): header =
+  Sexp_codec.wrap (fun () : header ->
+    {
+      name = Sexp_codec.to_string (Sexp_codec.field "name" j)
+    })
+
+let sexp_of_request (v: request): Sexplib0.Sexp.t =
+  Sexp_codec.obj [
+    ("method", sexp_of_method v.method_);
+    ("headers", (Sexp_codec.list sexp_of_header) v.headers)
+  ]
+and request_of_sexp (j: Sexplib0.Sexp.t): request =
+  Sexp_codec.wrap (fun () : request ->
+    {
+      method_ = (Sexp_codec.get method_of_sexp) (Sexp_codec.field "method" j);
+      headers = (Sexp_codec.to_list (Sexp_codec.get header_of_sexp)) (Sexp_codec.field "headers" j)
+    })
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_request: request = decode_request (R.Request.of_message empty_message)
+let default_header: header = decode_header (R.Header.of_message empty_message)
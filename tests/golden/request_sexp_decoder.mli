@@ -0,0 +1,33 @@
+module S := Request.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type method_ =
+  | Get
+  | Post
+
+type header =
+  {
+    name: string
+  }
+
+type request =
+  {
+    method_: method_;
+    headers: header list
+  }
+
+val decode_method: R.Method.t -> method_
+val encode_method: method_ -> B.Method.t
+val sexp_of_method: method_ -> Sexplib0.Sexp.t
+val method_of_sexp: Sexplib0.Sexp.t -> method_
+val decode_request: R.Request.t -> request
+val encode_request: B.Request.t -> request -> unit
+val sexp_of_request: request -> Sexplib0.Sexp.t
+val request_of_sexp: Sexplib0.Sexp.t -> request
+val default_request: request
+val decode_header: R.Header.t -> header
+val encode_header: B.Header.t -> header -> unit
+val sexp_of_header: header -> Sexplib0.Sexp.t
+val header_of_sexp: Sexplib0.Sexp.t -> header
+val default_header: header
@@ -0,0 +1,70 @@
+module S = Shapes.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type shape_rectangle =
+  {
+    width: float;
+    height: float;
+    union: shape_rectangle_union
+  }
+and shape_rectangle_union =
+  | Sharp
+  | Rounded of float
+
+(** A shape on the canvas.
+    Shapes are drawn in order. *)
+type shape =
+  {
+    name: string; (** The name shown in the legend. *)
+    union: shape_union
+  }
+and shape_union =
+  | Circle of {radius: float} (** A circle around the origin. *)
+  | Rectangle of shape_rectangle
+  | Label of string
+
+let rec decode_shape_rectangle r: shape_rectangle =
+  {
+    width = (R.Shape.Rectangle.width_get r);
+    height = (R.Shape.Rectangle.height_get r);
+    union = decode_shape_rectangle_union r
+  }
+and decode_shape_rectangle_union r: shape_rectangle_union =
+  match R.Shape.Rectangle.get r with
+  | Sharp -> Sharp
+  | Rounded r' -> Rounded (r')
+  | Undefined _ -> failwith "Undefined discriminant"
+
+let rec decode_shape r: shape =
+  {
+    name = (R.Shape.name_get r);
+    union = decode_shape_union r
+  }
+and decode_shape_union r: shape_union =
+  match R.Shape.get r with
+  | Circle r' -> Circle {radius = (R.Shape.Circle.radius_get r')}
+  | Rectangle r' -> Rectangle (decode_shape_rectangle r')
+  | Label r' -> Label (r')
+  | Undefined _ -> failwith "Undefined discriminant"
+
+let rec encode_shape_rectangle b (v: shape_rectangle): unit =
+  B.Shape.Rectangle.width_set b v.width;
+  B.Shape.Rectangle.height_set b v.height;
+  encode_shape_rectangle_union b v.union
+and encode_shape_rectangle_union b (v: shape_rectangle_union): unit =
+  match v with
+  | Sharp -> B.Shape.Rectangle.sharp_set b
+  | Rounded v' -> B.Shape.Rectangle.rounded_set b v'
+
+let rec encode_shape b (v: shape): unit =
+  B.Shape.name_set b v.name;
+  encode_shape_union b v.union
+and encode_shape_union b (v: shape_union): unit =
+  match v with
+  | Circle v' -> let b' = B.Shape.circle_init b in B.Shape.Circle.radius_set b' v'.radius
+  | Rectangle v' -> encode_shape_rectangle (B.Shape.rectangle_init b) v'
+  | Label v' -> B.Shape.label_set b v'
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_shape: shape = decode_shape (R.Shape.of_message empty_message)
@@ -0,0 +1,198 @@
+module S = Shapes.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+module Json_codec = struct
+  exception Invalid of string
+  let fail what j = raise (Invalid (what ^ ": " ^ Yojson.Safe.to_string j))
+  let wrap f = try Ok (f ()) with Invalid e -> Error e
+  let get f j = match f j with Ok x -> x | Error e -> raise (Invalid e)
+  let lift f j = wrap (fun () -> f j)
+  let default d f = function `Null -> d () | j -> f j
+  let unit () = `Null
+  let bool b = `Bool b
+  let int n = `Int n
+  let int32 n = `Int (Int32.to_int n)
+  let int64 n = `String (Int64.to_string n)
+  let uint32 n = `Int (Stdint.Uint32.to_int n)
+  let uint64 n = `String (Stdint.Uint64.to_string n)
+  let float x =
+    if Float.is_nan x then `String "NaN"
+    else if x = Float.infinity then `String "Infinity"
+    else if x = Float.neg_infinity then `String "-Infinity"
+    else `Float x
+  let string s = `String s
+  let data s = `List (List.init (String.length s) (fun i -> `Int (Char.code s.[i])))
+  let list f xs = `List (List.map f xs)
+  let array f xs = list f (Array.to_list xs)
+  let seq f xs = list f (List.of_seq xs)
+  let option f = function Some x -> f x | None -> `Null
+  let capability _ = `Null
+  let enumerant name = `String name
+  let obj members = `Assoc members
+  let field name = function
+    | `Assoc members -> Option.value (List.assoc_opt name members) ~default:`Null
+    | `Null -> `Null
+    | j -> fail "expected an object" j
+  let member names = function
+    | `Assoc members as j ->
+      (match List.find_opt (fun name -> List.mem_assoc name members) names with
+       | Some name -> name
+       | None -> fail "expected a union member" j)
+    | `Null -> ""
+    | j -> fail "expected an object" j
+  let to_unit _ = ()
+  let to_bool = function `Bool b -> b | `Null -> false | j -> fail "expected a boolean" j
+  let to_int = function `Int n -> n | `Null -> 0 | j -> fail "expected an integer" j
+  let to_int32 j =
+    let n = to_int j in
+    if Int32.to_int (Int32.of_int n) = n then Int32.of_int n
+    else fail "expected a 32-bit integer" j
+  let to_int64 = function
+    | (`String s | `Intlit s) as j ->
+      (match Int64.of_string_opt s with Some n -> n | None -> fail "expected a 64-bit integer" j)
+    | `Int n -> Int64.of_int n
+    | `Null -> 0L
+    | j -> fail "expected a 64-bit integer" j
+  let to_uint32 j =
+    let n = to_int j in
+    if n >= 0 && n <= 0xffff_ffff then Stdint.Uint32.of_int n
+    else fail "expected an unsigned 32-bit integer" j
+  let to_uint64 = function
+    | (`String s | `Intlit s) as j ->
+      (try Stdint.Uint64.of_string s with _ -> fail "expected an unsigned 64-bit integer" j)
+    | `Int n when n >= 0 -> Stdint.Uint64.of_int n
+    | `Null -> Stdint.Uint64.zero
+    | j -> fail "expected an unsigned 64-bit integer" j
+  let to_float = function
+    | `Float x -> x
+    | `Int n -> float_of_int n
+    | `String "NaN" -> Float.nan
+    | `String "Infinity" -> Float.infinity
+    | `String "-Infinity" -> Float.neg_infinity
+    | `Null -> 0.
+    | j -> fail "expected a number" j
+  let to_string = function `String s -> s | `Null -> "" | j -> fail "expected a string" j
+  let to_byte = function
+    | `Int n when n >= 0 && n < 256 -> Char.chr n
+    | j -> fail "expected a byte" j
+  let to_data = function
+    | `List js -> String.of_seq (Seq.map to_byte (List.to_seq js))
+    | `Null -> ""
+    | j -> fail "expected an array of bytes" j
+  let to_list f = function `List js -> List.map f js | `Null -> [] | j -> fail "expected an array" j
+  let to_array f j = Array.of_list (to_list f j)
+  let to_seq f j = List.to_seq (to_list f j)
+  let to_option f = function `Null -> None | j -> Some (f j)
+  let to_capability _ = None
+  let to_enumerant = function
+    | `String name -> `Name name
+    | `Int n -> `Ordinal n
+    | `Null -> `Ordinal 0
+    | j -> fail "expected an enumerant" j
+end [@@ocaml.warning "-32"]
+
+type shape_rectangle =
+  {
+    width: float;
+    height: float;
+    union: shape_rectangle_union
+  }
+and shape_rectangle_union =
+  | Sharp
+  | Rounded of float
+
+(** A shape on the canvas.
+    Shapes are drawn in order. *)
+type shape =
+  {
+    name: string; (** The name shown in the legend. *)
+    union: shape_union
+  }
+and shape_union =
+  | Circle of {radius: float} (** A circle around the origin. *)
+  | Rectangle of shape_rectangle
+  | Label of string
+
+let rec decode_shape_rectangle r: shape_rectangle =
+  {
+    width = (R.Shape.Rectangle.width_get r);
+    height = (R.Shape.Rectangle.height_get r);
+    union = decode_shape_rectangle_union r
+  }
+and decode_shape_rectangle_union r: shape_rectangle_union =
+  match R.Shape.Rectangle.get r with
+  | Sharp -> Sharp
+  | Rounded r' -> Rounded (r')
+  | Undefined _ -> failwith "Undefined discriminant"
+
+let rec decode_shape r: shape =
+  {
+    name = (R.Shape.name_get r);
+    union = decode_shape_union r
+  }
+and decode_shape_union r: shape_union =
+  match R.Shape.get r with
+  | Circle r' -> Circle {radius = (R.Shape.Circle.radius_get r')}
+  | Rectangle r' -> Rectangle (decode_shape_rectangle r')
+  | Label r' -> Label (r')
+  | Undefined _ -> failwith "Undefined discriminant"
+
+let rec encode_shape_rectangle b (v: shape_rectangle): unit =
+  B.Shape.Rectangle.width_set b v.width;
+  B.Shape.Rectangle.height_set b v.height;
+  encode_shape_rectangle_union b v.union
+and encode_shape_rectangle_union b (v: shape_rectangle_union): unit =
+  match v with
+  | Sharp -> B.Shape.Rectangle.sharp_set b
+  | Rounded v' -> B.Shape.Rectangle.rounded_set b v'
+
+let rec encode_shape b (v: shape): unit =
+  B.Shape.name_set b v.name;
+  encode_shape_union b v.union
+and encode_shape_union b (v: shape_union): unit =
+  match v with
+  | Circle v' -> let b' = B.Shape.circle_init b in B.Shape.Circle.radius_set b' v'.radius
+  | Rectangle v' -> encode_shape_rectangle (B.Shape.rectangle_init b) v'
+  | Label v' -> B.Shape.label_set b v'
+
+let shape_rectangle_to_yojson (v: shape_rectangle): Yojson.Safe.t =
+  Json_codec.obj [
+    ("width", Json_codec.float v.width);
+    ("height", Json_codec.float v.height);
+    (match v.union with
+      | Sharp -> ("sharp", Json_codec.unit ())
+      | Rounded v' -> ("rounded", Json_codec.float v'))
+  ]
+and shape_rectangle_of_yojson (j: Yojson.Safe.t): (shape_rectangle, string) result =
+  Json_codec.wrap (fun () : shape_rectangle ->
+    {
+      width = Json_codec.to_float (Json_codec.field "width" j);
+      height = Json_codec.to_float (Json_codec.field "height" j);
+      union = (match Json_codec.member ["sharp"; "rounded"] j with
+      | "rounded" -> Rounded (Json_codec.to_float (Json_codec.field "rounded" j))
+      | "sharp" | "" -> Sharp
+      | _ -> Json_codec.fail "undefined union member" j)
+    })
+
+let shape_to_yojson (v: shape): Yojson.Safe.t =
+  Json_codec.obj [
+    ("name", Json_codec.string v.name);
+    (match v.union with
+      | Circle v' -> ("circle", Json_codec.obj [("radius", Json_codec.float v'.radius)])
+      | Rectangle v' -> ("rectangle", shape_rectangle_to_yojson v')
+      | Label v' -> ("label", Json_codec.string v'))
+  ]
+and shape_of_yojson (j: Yojson.Safe.t): (shape, string) result =
+  Json_codec.wrap (fun () : shape ->
+    {
+      name = Json_codec.to_string (Json_codec.field "name" j);
+      union = (match Json_codec.member ["circle"; "rectangle"; "label"] j with
+      | "rectangle" -> Rectangle (Json_codec.get shape_rectangle_of_yojson (Json_codec.field "rectangle" j))
+      | "label" -> Label (Json_codec.to_string (Json_codec.field "label" j))
+      | "circle" | "" -> Circle {radius = Json_codec.to_float (Json_codec.field "radius" (Json_codec.field "circle" j))}
+      | _ -> Json_codec.fail "undefined union member" j)
+    })
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_shape: shape = decode_shape (R.Shape.of_message empty_message)
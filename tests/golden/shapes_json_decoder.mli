@@ -0,0 +1,42 @@
+module S := Shapes.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type shape_rectangle =
+  {
+    width: float;
+    height: float;
+    union: shape_rectangle_union
+  }
+and shape_rectangle_union =
+  | Sharp
+  | Rounded of float
+
+(** A shape on the canvas.
+    Shapes are drawn in order. *)
+type shape =
+  {
+    name: string; (** The name shown in the legend. *)
+    union: shape_union
+  }
+and shape_union =
+  | Circle of {radius: float} (** A circle around the origin. *)
+  | Rectangle of shape_rectangle
+  | Label of string
+
+val decode_shape_rectangle: R.Shape.Rectangle.t -> shape_rectangle
+val encode_shape_rectangle: B.Shape.Rectangle.t -> shape_rectangle -> unit
+val decode_shape_rectangle_union: R.Shape.Rectangle.t -> shape_rectangle_union
+val encode_shape_rectangle_union: B.Shape.Rectangle.t -> shape_rectangle_union -> unit
+val shape_rectangle_to_yojson: shape_rectangle -> Yojson.Safe.t
+val shape_rectangle_of_yojson: Yojson.Safe.t -> (shape_rectangle, string) result
+
+(** A shape on the canvas.
+    Shapes are drawn in order. *)
+val decode_shape: R.Shape.t -> shape
+val encode_shape: B.Shape.t -> shape -> unit
+val decode_shape_union: R.Shape.t -> shape_union
+val encode_shape_union: B.Shape.t -> shape_union -> unit
+val shape_to_yojson: shape -> Yojson.Safe.t
+val shape_of_yojson: Yojson.Safe.t -> (shape, string) result
+val default_shape: shape
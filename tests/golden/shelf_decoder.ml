@@ -0,0 +1,50 @@
+module S = Shelf.Make (Capnp.BytesMessage)
+module R = S.Reader
+module B = S.Builder
+
+type 't box =
+  {
+    value: 't
+  }
+
+type book =
+  {
+    title: string
+  }
+
+type shelf =
+  {
+    box: book box;
+    boxes: book box list
+  }
+
+let decode_box: 't. (R.pointer_t -> 't) -> R.Box.t -> 't box = fun decode_t r ->
+  {
+    value = decode_t (R.Box.value_get r)
+  }
+
+let decode_book r: book =
+  {
+    title = (R.Book.title_get r)
+  }
+
+let decode_shelf r: shelf =
+  {
+    box = decode_box (fun p -> decode_book (R.of_pointer p)) (R.Shelf.box_get r);
+    boxes = Capnp.Array.map_list (R.Shelf.boxes_get r) ~f:(decode_box (fun p -> decode_book (R.of_pointer p)))
+  }
+
+let encode_box: 't. (B.pointer_t -> 't -> unit) -> B.Box.t -> 't box -> unit = fun encode_t b v ->
+  encode_t (B.Box.value_get b) v.value
+
+let encode_book b (v: book): unit =
+  B.Book.title_set b v.title
+
+let encode_shelf b (v: shelf): unit =
+  encode_box (fun p v -> encode_book (B.Book.init_pointer p) v) (B.Shelf.box_init b) v.box;
+  (let a = B.Shelf.boxes_init b (List.length v.boxes) in List.iteri (fun i v' -> encode_box (fun p v -> encode_book (B.Book.init_pointer p) v) (Capnp.Array.get a i) v') v.boxes)
+
+let empty_message = Capnp.BytesMessage.Message.of_storage [Bytes.of_string "\xfc\xff\xff\xff\x00\x00\x00\x00"]
+let default_box decode_t = decode_box decode_t (R.Box.of_message empty_message)
+let default_book: book = decode_book (R.Book.of_message empty_message)
+let default_shelf: shelf = decode_shelf (R.Shelf.of_message empty_message)
@@ -0,0 +1,29 @@
+module S := Shelf.Make (Capnp.BytesMessage)
+module R := S.Reader
+module B := S.Builder
+
+type 't box =
+  {
+    value: 't
+  }
+
+type book =
+  {
+    title: string
+  }
+
+type shelf =
+  {
+    box: book box;
+    boxes: book box list
+  }
+
+val decode_box: (R.pointer_t -> 't) -> R.Box.t -> 't box
+val encode_box: (B.pointer_t -> 't -> unit) -> B.Box.t -> 't box -> unit
+val default_box: (R.pointer_t -> 't) -> 't box
+val decode_book: R.Book.t -> book
+val encode_book: B.Book.t -> book -> unit
+val default_book: book
+val decode_shelf: R.Shelf.t -> shelf
+val encode_shelf: B.Shelf.t -> shelf -> unit
+val default_shelf: shelf